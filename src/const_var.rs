@@ -39,10 +39,13 @@ pub const MLOAD: u8 = 0x51;
 pub const MSTORE: u8 = 0x52;
 pub const MSTORE8: u8 = 0x53;
 pub const MSIZE: u8 = 0x59;
+pub const MCOPY: u8 = 0x5E;
 
 // 存储指令
 pub const SLOAD: u8 = 0x54;
 pub const SSTORE: u8 = 0x55;
+pub const TLOAD: u8 = 0x5C;
+pub const TSTORE: u8 = 0x5D;
 
 // 控制指令
 pub const JUMP: u8 = 0x56;
@@ -61,6 +64,8 @@ pub const GASLIMIT: u8 = 0x45;
 pub const CHAINID: u8 = 0x46;
 pub const SELFBALANCE: u8 = 0x47;
 pub const BASEFEE: u8 = 0x48;
+pub const BLOBHASH: u8 = 0x49;
+pub const BLOBBASEFEE: u8 = 0x4A;
 
 // 堆栈指令
 pub const DUP1: u8 = 0x80;
@@ -104,6 +109,7 @@ pub const INVALID: u8 = 0xfe;
 
 // 调用指令
 pub const CALL: u8 = 0xf1;
+pub const CALLCODE: u8 = 0xf2;
 pub const DELEGATECALL: u8 = 0xf4;
 pub const STATICCALL: u8 = 0xfa;
 
@@ -115,3 +121,27 @@ pub const SELFDESTRUCT: u8 = 0xff;
 // GAS指令
 pub const GAS: u8 = 0x5a;
 
+// 转账调用时子调用获得的gas津贴
+pub const CALL_STIPEND: u32 = 2300;
+// 带value的调用额外消耗的gas
+pub const CALL_VALUE_GAS: u32 = 9000;
+// 转账创建新账户额外消耗的gas
+pub const NEW_ACCOUNT_GAS: u32 = 25000;
+
+// 交易的固有gas
+pub const TX_BASE_GAS: u32 = 21000;
+// 创建合约的交易额外消耗的gas
+pub const TX_CREATE_GAS: u32 = 32000;
+// 交易数据中每个0字节和非0字节的gas
+pub const TX_DATA_ZERO_GAS: u32 = 4;
+pub const TX_DATA_NON_ZERO_GAS: u32 = 16;
+// EIP-3860 初始代码每32字节的gas
+pub const INIT_CODE_WORD_GAS: u32 = 2;
+
+// EIP-4844 blob gas价格参数
+pub const MIN_BLOB_BASE_FEE: u32 = 1;
+pub const BLOB_BASE_FEE_UPDATE_FRACTION: u32 = 3338477;
+
+// BLOCKHASH可查询的历史区块数量
+pub const BLOCK_HASH_HISTORY: u32 = 256;
+
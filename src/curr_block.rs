@@ -1,57 +1,306 @@
-/// TODO
-/// 实现区块链，修改成从链上读取
+/// 区块环境
+/// 包含执行交易时区块相关指令（COINBASE、NUMBER、PREVRANDAO等）读取的区块头字段，
+/// 可以通过with_*方法逐个设置，也可以从t8n格式的env.json加载。
+use std::collections::HashMap;
+use std::path::Path;
+
 use num_bigint::BigUint;
+use num_traits::Zero;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::const_var::{BLOB_BASE_FEE_UPDATE_FRACTION, MIN_BLOB_BASE_FEE};
+use crate::genesis::{
+    parse_address, parse_b256, parse_optional_quantity, parse_quantity, GenesisError,
+};
+use crate::primitives::{Address, B256};
+
+/// EIP-4844中以泰勒展开近似计算 factor * e ** (numerator / denominator)
+pub fn fake_exponential(factor: &BigUint, numerator: &BigUint, denominator: &BigUint) -> BigUint {
+    let mut i = 1u32;
+    let mut output = BigUint::zero();
+    let mut numerator_accum = factor * denominator;
+    while !numerator_accum.is_zero() {
+        output += &numerator_accum;
+        numerator_accum = numerator_accum * numerator / (denominator * i);
+        i += 1;
+    }
+    output / denominator
+}
+
+/// t8n工具env.json的原始JSON结构
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvJson {
+    current_coinbase: Option<String>,
+    current_gas_limit: Option<Value>,
+    current_number: Option<Value>,
+    current_timestamp: Option<Value>,
+    current_difficulty: Option<Value>,
+    current_random: Option<String>,
+    current_base_fee: Option<Value>,
+    current_excess_blob_gas: Option<Value>,
+    parent_beacon_block_root: Option<String>,
+    #[serde(default)]
+    block_hashes: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone)]
-pub struct CurrentBlock {
-    blockhash: BigUint,
-    coinbase: BigUint,
-    timestamp: BigUint,
+pub struct BlockEnv {
     number: BigUint,
-    prevrandao: BigUint,
-    gaslimit: BigUint,
-    chainid: BigUint,
-    selfbalance: BigUint,
-    basefee: BigUint,
+    timestamp: BigUint,
+    coinbase: Address,
+    gas_limit: BigUint,
+    base_fee: BigUint,
+    // 合并前PREVRANDAO返回difficulty
+    difficulty: BigUint,
+    // 合并后PREVRANDAO返回prevrandao（即区块头的mixHash）
+    prevrandao: Option<B256>,
+    // 超出目标的blob gas，用于计算blob的基础费用
+    excess_blob_gas: BigUint,
+    parent_beacon_block_root: Option<B256>,
+    chain_id: BigUint,
+    // 环境中提供的历史区块哈希，优先于数据库
+    block_hashes: HashMap<BigUint, B256>,
 }
-impl CurrentBlock {
-    pub fn init() -> Self {
+
+impl Default for BlockEnv {
+    fn default() -> Self {
         Self {
-            blockhash: BigUint::from(0x7527123fc877fe753b3122dc592671bu128),
-            coinbase: BigUint::from(0x388C818CA8B9251b393131C08a736A67u128),
-            timestamp: BigUint::from(1625900000u128),
-            number: BigUint::from(17871709u128),
-            prevrandao: BigUint::from(0xce124dee50136f3f93f19667fb4u128),
-            gaslimit: BigUint::from(30_u8),
-            chainid: BigUint::from(1u8),
-            selfbalance: BigUint::from(100u8),
-            basefee: BigUint::from(30_u8),
+            number: BigUint::zero(),
+            timestamp: BigUint::zero(),
+            coinbase: Address::ZERO,
+            gas_limit: BigUint::from(30_000_000u32),
+            base_fee: BigUint::zero(),
+            difficulty: BigUint::zero(),
+            prevrandao: None,
+            excess_blob_gas: BigUint::zero(),
+            parent_beacon_block_root: None,
+            chain_id: BigUint::from(1u8),
+            block_hashes: HashMap::new(),
         }
     }
-    pub fn get_block_hash(&self) -> &BigUint {
-        &self.blockhash
+}
+
+impl BlockEnv {
+    /// 测试用的主网区块环境
+    pub fn init() -> Self {
+        Self::default()
+            .with_number(BigUint::from(17871709u32))
+            .with_timestamp(BigUint::from(1625900000u32))
+            .with_coinbase(Address::from_word(&BigUint::from(
+                0x388c818ca8b9251b393131c08a736a67u128,
+            )))
+            .with_prevrandao(B256::from_word(&BigUint::from(
+                0xce124dee50136f3f93f19667fb4u128,
+            )))
+            .with_base_fee(BigUint::from(30u8))
     }
-    pub fn get_coinbase(&self) -> &BigUint {
-        &self.coinbase
+
+    /// 解析t8n格式的env.json
+    /// ```
+    /// use mini_evm::curr_block::BlockEnv;
+    /// use num_bigint::BigUint;
+    /// let env = BlockEnv::from_env_json(r#"{
+    ///     "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+    ///     "currentGasLimit": "0x016345785d8a0000",
+    ///     "currentNumber": "0x01",
+    ///     "currentTimestamp": "0x03e8",
+    ///     "currentRandom": "0x0000000000000000000000000000000000000000000000000000000000020000",
+    ///     "currentBaseFee": "0x0a",
+    ///     "blockHashes": {"0": "0xe729de3fec21e30bea3d56adb01ed14bc107273c2775f9355afb10f594a10d9e"}
+    /// }"#).unwrap();
+    /// assert_eq!(env.get_number(), &BigUint::from(1u8));
+    /// assert_eq!(env.get_prevrandao(), BigUint::from(0x20000u32));
+    /// ```
+    pub fn from_env_json(json: &str) -> Result<Self, GenesisError> {
+        let raw: EnvJson = serde_json::from_str(json)?;
+        let mut env = Self::default()
+            .with_number(parse_optional_quantity(&raw.current_number)?)
+            .with_timestamp(parse_optional_quantity(&raw.current_timestamp)?)
+            .with_base_fee(parse_optional_quantity(&raw.current_base_fee)?)
+            .with_difficulty(parse_optional_quantity(&raw.current_difficulty)?)
+            .with_excess_blob_gas(parse_optional_quantity(&raw.current_excess_blob_gas)?);
+        if let Some(coinbase) = &raw.current_coinbase {
+            env = env.with_coinbase(parse_address(coinbase)?);
+        }
+        if let Some(gas_limit) = &raw.current_gas_limit {
+            env = env.with_gas_limit(parse_quantity(gas_limit)?);
+        }
+        if let Some(random) = &raw.current_random {
+            env = env.with_prevrandao(parse_b256(random)?);
+        }
+        if let Some(root) = &raw.parent_beacon_block_root {
+            env = env.with_parent_beacon_block_root(parse_b256(root)?);
+        }
+        for (number, hash) in &raw.block_hashes {
+            env.insert_block_hash(
+                parse_quantity(&Value::String(number.clone()))?,
+                parse_b256(hash)?,
+            );
+        }
+        Ok(env)
     }
-    pub fn get_timestamp(&self) -> &BigUint {
-        &self.timestamp
+
+    /// 读取env.json文件
+    pub fn from_env_file(path: impl AsRef<Path>) -> Result<Self, GenesisError> {
+        Self::from_env_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn with_number(mut self, number: BigUint) -> Self {
+        self.number = number;
+        self
+    }
+    pub fn with_timestamp(mut self, timestamp: BigUint) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    pub fn with_coinbase(mut self, coinbase: Address) -> Self {
+        self.coinbase = coinbase;
+        self
+    }
+    pub fn with_gas_limit(mut self, gas_limit: BigUint) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+    pub fn with_base_fee(mut self, base_fee: BigUint) -> Self {
+        self.base_fee = base_fee;
+        self
+    }
+    pub fn with_difficulty(mut self, difficulty: BigUint) -> Self {
+        self.difficulty = difficulty;
+        self
     }
+    pub fn with_prevrandao(mut self, prevrandao: B256) -> Self {
+        self.prevrandao = Some(prevrandao);
+        self
+    }
+    pub fn with_excess_blob_gas(mut self, excess_blob_gas: BigUint) -> Self {
+        self.excess_blob_gas = excess_blob_gas;
+        self
+    }
+    pub fn with_parent_beacon_block_root(mut self, root: B256) -> Self {
+        self.parent_beacon_block_root = Some(root);
+        self
+    }
+    pub fn with_chain_id(mut self, chain_id: BigUint) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
     pub fn get_number(&self) -> &BigUint {
         &self.number
     }
-    pub fn get_prevrandao(&self) -> &BigUint {
-        &self.prevrandao
+    pub fn get_timestamp(&self) -> &BigUint {
+        &self.timestamp
+    }
+    pub fn get_coinbase(&self) -> &Address {
+        &self.coinbase
     }
     pub fn get_gaslimit(&self) -> &BigUint {
-        &self.gaslimit
+        &self.gas_limit
+    }
+    pub fn get_basefee(&self) -> &BigUint {
+        &self.base_fee
+    }
+    pub fn get_difficulty(&self) -> &BigUint {
+        &self.difficulty
+    }
+    /// PREVRANDAO的值，未设置prevrandao时为difficulty
+    pub fn get_prevrandao(&self) -> BigUint {
+        match &self.prevrandao {
+            Some(prevrandao) => prevrandao.to_word(),
+            None => self.difficulty.clone(),
+        }
+    }
+    pub fn get_parent_beacon_block_root(&self) -> Option<&B256> {
+        self.parent_beacon_block_root.as_ref()
     }
     pub fn get_chainid(&self) -> &BigUint {
-        &self.chainid
+        &self.chain_id
     }
-    pub fn get_selfbalance(&self) -> &BigUint {
-        &self.selfbalance
+    pub fn get_excess_blob_gas(&self) -> &BigUint {
+        &self.excess_blob_gas
     }
-    pub fn get_basefee(&self) -> &BigUint {
-        &self.basefee
+    pub fn set_excess_blob_gas(&mut self, excess_blob_gas: BigUint) {
+        self.excess_blob_gas = excess_blob_gas;
+    }
+    pub fn get_block_hash(&self, number: &BigUint) -> Option<&B256> {
+        self.block_hashes.get(number)
+    }
+    pub fn insert_block_hash(&mut self, number: BigUint, hash: B256) {
+        self.block_hashes.insert(number, hash);
+    }
+    /// blob的基础费用
+    /// ```
+    /// use mini_evm::curr_block::BlockEnv;
+    /// use num_bigint::BigUint;
+    /// let mut block = BlockEnv::init();
+    /// assert_eq!(block.get_blob_basefee(), BigUint::from(1u8));
+    /// block.set_excess_blob_gas(BigUint::from(10_000_000u32));
+    /// assert_eq!(block.get_blob_basefee(), BigUint::from(19u8));
+    /// ```
+    pub fn get_blob_basefee(&self) -> BigUint {
+        fake_exponential(
+            &BigUint::from(MIN_BLOB_BASE_FEE),
+            &self.excess_blob_gas,
+            &BigUint::from(BLOB_BASE_FEE_UPDATE_FRACTION),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+
+    #[test]
+    fn test_env_file() {
+        let env = BlockEnv::from_env_file("fixtures/env.json").unwrap();
+        assert_eq!(env.get_number(), &BigUint::from(100u8));
+        assert_eq!(env.get_timestamp(), &BigUint::from(0x65f1b057u32));
+        assert_eq!(
+            env.get_coinbase(),
+            &"0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert_eq!(env.get_gaslimit(), &BigUint::from(30_000_000u32));
+        assert_eq!(env.get_basefee(), &BigUint::from(7u8));
+        assert_eq!(env.get_parent_beacon_block_root(), Some(&B256::ZERO));
+        assert_eq!(
+            env.get_prevrandao(),
+            BigUint::parse_bytes(
+                b"a86c2e601b6c44eb4848f7d23d9df3113fbcac42041c49cbed5000cb4f118777",
+                16
+            )
+            .unwrap()
+        );
+
+        // NUMBER-1的哈希来自env中的blockHashes
+        Lazy::force(&INIT_LOG);
+        let mut evm_test = Evm::init_evm(hex::decode("6001430340").unwrap(), Transaction::mock());
+        evm_test.current_block = env.clone();
+        evm_test.run();
+        assert_eq!(
+            hex::encode(&evm_test.stack.get(1).data),
+            "0ba7c2bba9dfd9b2b7a3f1d4a5d8d1b2c1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6"
+        );
+    }
+
+    #[test]
+    fn test_prevrandao_fallback() {
+        // 未提供currentRandom时使用difficulty
+        let env = BlockEnv::from_env_json(r#"{"currentDifficulty": "0x20000"}"#).unwrap();
+        assert_eq!(env.get_prevrandao(), BigUint::from(0x20000u32));
+        assert_eq!(env.get_gaslimit(), &BigUint::from(30_000_000u32));
+        assert!(BlockEnv::from_env_json(r#"{"currentNumber": "0xzz"}"#).is_err());
+
+        let env = BlockEnv::default()
+            .with_difficulty(BigUint::from(1u8))
+            .with_prevrandao(B256::from([2u8; 32]));
+        assert_eq!(env.get_prevrandao(), B256::from([2u8; 32]).to_word());
     }
 }
@@ -0,0 +1,339 @@
+/// 带缓存的数据库
+/// CacheDb在首次访问时从StateProvider获取账户、代码、存储和区块哈希并缓存，
+/// 之后的读取和所有写入都只发生在本地缓存中，不会修改provider的状态，
+/// 可用于在主网状态的分叉上模拟交易。
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug};
+
+use num_bigint::BigUint;
+
+use super::{AccountInfo, Database, DatabaseCommit};
+use crate::fake_db::AccountDb;
+use crate::primitives::{Address, B256};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    // 与后端通信失败
+    Transport(String),
+    // 后端返回的数据无法解析
+    InvalidResponse(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Transport(msg) => write!(f, "provider transport error: {}", msg),
+            ProviderError::InvalidResponse(msg) => {
+                write!(f, "invalid provider response: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// 远程状态的来源，例如JSON-RPC节点或本地的状态文件
+pub trait StateProvider: Debug {
+    /// 账户基本信息，账户不存在时返回None
+    fn account(&mut self, address: &Address) -> Result<Option<AccountInfo>, ProviderError>;
+    /// 根据代码哈希获取合约代码
+    fn code_by_hash(&mut self, code_hash: &B256) -> Result<Vec<u8>, ProviderError>;
+    /// 账户存储槽的值
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> Result<BigUint, ProviderError>;
+    /// 区块号对应的区块哈希
+    fn block_hash(&mut self, number: &BigUint) -> Result<B256, ProviderError>;
+}
+
+/// 本地状态作为provider，可以通过AccountDb::load或Genesis::to_db从文件加载
+impl StateProvider for AccountDb {
+    fn account(&mut self, address: &Address) -> Result<Option<AccountInfo>, ProviderError> {
+        Ok(Database::basic(self, address))
+    }
+    fn code_by_hash(&mut self, code_hash: &B256) -> Result<Vec<u8>, ProviderError> {
+        Ok(Database::code_by_hash(self, code_hash))
+    }
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> Result<BigUint, ProviderError> {
+        Ok(Database::storage(self, address, slot))
+    }
+    fn block_hash(&mut self, number: &BigUint) -> Result<B256, ProviderError> {
+        Ok(Database::block_hash(self, number))
+    }
+}
+
+/// 缓存数据库
+/// Database接口不返回错误，provider出错时panic
+/// ```
+/// use mini_evm::db::cache::CacheDb;
+/// use mini_evm::db::{shared, Database};
+/// use mini_evm::evm::Evm;
+/// use mini_evm::fake_db::AccountDb;
+/// use mini_evm::transaction::Transaction;
+/// let db = shared(CacheDb::new(AccountDb::mock()));
+/// let mut evm_test = Evm::init_evm_with_db(hex::decode("60f1600155").unwrap(), Transaction::mock(), db.clone());
+/// evm_test.run();
+/// let address = Transaction::mock().get_this_addr();
+/// assert_eq!(db.borrow_mut().storage(&address, &1u8.into()), 0xf1u8.into());
+/// ```
+#[derive(Debug)]
+pub struct CacheDb<P> {
+    provider: P,
+    // 值为None表示账户不存在或已被删除
+    accounts: HashMap<Address, Option<AccountInfo>>,
+    contracts: HashMap<B256, Vec<u8>>,
+    storage: HashMap<Address, HashMap<BigUint, BigUint>>,
+    block_hashes: HashMap<BigUint, B256>,
+    // 本地删除过的账户，其存储不再从provider读取
+    destroyed: HashSet<Address>,
+}
+
+impl<P: StateProvider> CacheDb<P> {
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            accounts: HashMap::new(),
+            contracts: HashMap::new(),
+            storage: HashMap::new(),
+            block_hashes: HashMap::new(),
+            destroyed: HashSet::new(),
+        }
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    pub fn into_provider(self) -> P {
+        self.provider
+    }
+}
+
+impl<P: StateProvider> Database for CacheDb<P> {
+    fn basic(&mut self, address: &Address) -> Option<AccountInfo> {
+        if let Some(info) = self.accounts.get(address) {
+            return info.clone();
+        }
+        let info = self
+            .provider
+            .account(address)
+            .unwrap_or_else(|err| panic!("failed to fetch account {}: {}", address, err));
+        if let Some(AccountInfo {
+            code_hash,
+            code: Some(code),
+            ..
+        }) = &info
+        {
+            self.contracts.insert(*code_hash, code.clone());
+        }
+        self.accounts.insert(*address, info.clone());
+        info
+    }
+    fn code_by_hash(&mut self, code_hash: &B256) -> Vec<u8> {
+        if let Some(code) = self.contracts.get(code_hash) {
+            return code.clone();
+        }
+        let code = self
+            .provider
+            .code_by_hash(code_hash)
+            .unwrap_or_else(|err| panic!("failed to fetch code {}: {}", code_hash, err));
+        self.contracts.insert(*code_hash, code.clone());
+        code
+    }
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> BigUint {
+        if let Some(value) = self.storage.get(address).and_then(|slots| slots.get(slot)) {
+            return value.clone();
+        }
+        let value = if self.destroyed.contains(address) {
+            BigUint::from(0u8)
+        } else {
+            self.provider
+                .storage(address, slot)
+                .unwrap_or_else(|err| panic!("failed to fetch storage of {}: {}", address, err))
+        };
+        self.storage
+            .entry(*address)
+            .or_default()
+            .insert(slot.clone(), value.clone());
+        value
+    }
+    fn block_hash(&mut self, number: &BigUint) -> B256 {
+        if let Some(hash) = self.block_hashes.get(number) {
+            return *hash;
+        }
+        let hash = self
+            .provider
+            .block_hash(number)
+            .unwrap_or_else(|err| panic!("failed to fetch block hash {}: {}", number, err));
+        self.block_hashes.insert(number.clone(), hash);
+        hash
+    }
+}
+
+impl<P: StateProvider> DatabaseCommit for CacheDb<P> {
+    fn insert_account(&mut self, address: &Address, info: AccountInfo) {
+        if let Some(code) = &info.code {
+            self.contracts.insert(info.code_hash, code.clone());
+        }
+        self.accounts.insert(*address, Some(info));
+    }
+    fn insert_storage(&mut self, address: &Address, slot: BigUint, value: BigUint) {
+        self.storage
+            .entry(*address)
+            .or_default()
+            .insert(slot, value);
+    }
+    fn remove_account(&mut self, address: &Address) {
+        self.accounts.insert(*address, None);
+        self.storage.remove(address);
+        self.destroyed.insert(*address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// 记录请求次数的provider
+    #[derive(Debug)]
+    struct MockProvider {
+        state: AccountDb,
+        requests: usize,
+    }
+
+    impl MockProvider {
+        fn new() -> Self {
+            let mut state = AccountDb::mock();
+            state.insert_block_hash(BigUint::from(7u8), B256::from([7u8; 32]));
+            Self { state, requests: 0 }
+        }
+    }
+
+    impl StateProvider for MockProvider {
+        fn account(&mut self, address: &Address) -> Result<Option<AccountInfo>, ProviderError> {
+            self.requests += 1;
+            // 模拟只返回代码哈希的后端
+            Ok(self.state.account(address)?.map(|mut info| {
+                info.code = None;
+                info
+            }))
+        }
+        fn code_by_hash(&mut self, code_hash: &B256) -> Result<Vec<u8>, ProviderError> {
+            self.requests += 1;
+            StateProvider::code_by_hash(&mut self.state, code_hash)
+        }
+        fn storage(&mut self, address: &Address, slot: &BigUint) -> Result<BigUint, ProviderError> {
+            self.requests += 1;
+            StateProvider::storage(&mut self.state, address, slot)
+        }
+        fn block_hash(&mut self, number: &BigUint) -> Result<B256, ProviderError> {
+            self.requests += 1;
+            StateProvider::block_hash(&mut self.state, number)
+        }
+    }
+
+    fn contract() -> Address {
+        "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lazy_fetch() {
+        let mut db = CacheDb::new(MockProvider::new());
+        let info = db.basic(&contract()).unwrap();
+        assert_eq!(
+            db.code_by_hash(&info.code_hash),
+            vec![0x60, 0x00, 0x60, 0x00]
+        );
+        assert_eq!(
+            db.storage(&contract(), &BigUint::from(1u8)),
+            BigUint::from(0u8)
+        );
+        assert_eq!(db.block_hash(&BigUint::from(7u8)), B256::from([7u8; 32]));
+        assert_eq!(db.provider().requests, 4);
+
+        // 再次读取命中缓存
+        assert_eq!(db.basic(&contract()), Some(info.clone()));
+        db.code_by_hash(&info.code_hash);
+        db.storage(&contract(), &BigUint::from(1u8));
+        db.block_hash(&BigUint::from(7u8));
+        assert_eq!(db.provider().requests, 4);
+
+        // 不存在的账户也会被缓存
+        let absent = "0x2000000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        assert_eq!(db.basic(&absent), None);
+        assert_eq!(db.basic(&absent), None);
+        assert_eq!(db.provider().requests, 5);
+    }
+
+    #[test]
+    fn test_local_writes() {
+        let mut db = CacheDb::new(MockProvider::new());
+        db.insert_storage(&contract(), BigUint::from(1u8), BigUint::from(2u8));
+        db.insert_account(
+            &contract(),
+            AccountInfo::new(BigUint::from(1u8), BigUint::from(2u8), vec![0x00]),
+        );
+        assert_eq!(
+            db.storage(&contract(), &BigUint::from(1u8)),
+            BigUint::from(2u8)
+        );
+        assert_eq!(db.basic(&contract()).unwrap().balance, BigUint::from(1u8));
+        assert_eq!(db.provider().requests, 0);
+
+        // 删除账户后存储为0，且不再请求provider
+        db.remove_account(&contract());
+        assert_eq!(db.basic(&contract()), None);
+        assert_eq!(
+            db.storage(&contract(), &BigUint::from(1u8)),
+            BigUint::from(0u8)
+        );
+        assert_eq!(
+            db.storage(&contract(), &BigUint::from(9u8)),
+            BigUint::from(0u8)
+        );
+        assert_eq!(db.provider().requests, 0);
+
+        // provider的状态不受影响
+        let mut provider = db.into_provider();
+        assert_eq!(
+            StateProvider::account(&mut provider.state, &contract())
+                .unwrap()
+                .unwrap()
+                .balance,
+            BigUint::from(100u8)
+        );
+    }
+
+    #[test]
+    fn test_fork_execution() {
+        Lazy::force(&INIT_LOG);
+        let db = Rc::new(RefCell::new(CacheDb::new(MockProvider::new())));
+        // 计数器逻辑 slot0 = slot0 + 1，执行两次
+        for _ in 0..2 {
+            let mut evm_test = Evm::init_evm_with_db(
+                hex::decode("600054600101600055").unwrap(),
+                Transaction::mock(),
+                db.clone(),
+            );
+            evm_test.run();
+            assert!(evm_test.success);
+        }
+        let address = Transaction::mock().get_this_addr();
+        let mut db = db.borrow_mut();
+        assert_eq!(
+            db.storage(&address, &BigUint::from(0u8)),
+            BigUint::from(2u8)
+        );
+        assert_eq!(
+            StateProvider::storage(&mut db.provider.state, &address, &BigUint::from(0u8)),
+            Ok(BigUint::from(0u8))
+        );
+    }
+}
@@ -0,0 +1,154 @@
+/// 世界状态数据库接口
+/// 虚拟机通过Database读取账户、代码、存储和区块哈希，通过DatabaseCommit写回状态，
+/// 所有指令共用注入到Evm中的同一个数据库实例。
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use num_bigint::BigUint;
+
+use crate::primitives::{Address, B256};
+use crate::utils::keccak256;
+
+pub mod cache;
+
+/// 空代码的哈希 keccak256("")
+pub const KECCAK_EMPTY: B256 = B256([
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+]);
+
+/// 账户基本信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountInfo {
+    pub balance: BigUint,
+    pub nonce: BigUint,
+    pub code_hash: B256,
+    // 合约代码，为None时需要通过code_by_hash获取
+    pub code: Option<Vec<u8>>,
+}
+
+impl AccountInfo {
+    pub fn new(balance: BigUint, nonce: BigUint, code: Vec<u8>) -> Self {
+        Self {
+            balance,
+            nonce,
+            code_hash: B256::from(keccak256(&code)),
+            code: Some(code),
+        }
+    }
+}
+
+impl Default for AccountInfo {
+    fn default() -> Self {
+        Self {
+            balance: BigUint::from(0u8),
+            nonce: BigUint::from(0u8),
+            code_hash: KECCAK_EMPTY,
+            code: Some(Vec::new()),
+        }
+    }
+}
+
+/// 读取世界状态
+pub trait Database {
+    /// 账户基本信息，账户不存在时返回None
+    fn basic(&mut self, address: &Address) -> Option<AccountInfo>;
+    /// 根据代码哈希获取合约代码
+    fn code_by_hash(&mut self, code_hash: &B256) -> Vec<u8>;
+    /// 账户存储槽的值，未写入的槽为0
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> BigUint;
+    /// 区块号对应的区块哈希
+    fn block_hash(&mut self, number: &BigUint) -> B256;
+}
+
+/// 写回世界状态
+pub trait DatabaseCommit {
+    /// 插入或覆盖账户信息，不影响已有的存储
+    fn insert_account(&mut self, address: &Address, info: AccountInfo);
+    /// 写入存储槽
+    fn insert_storage(&mut self, address: &Address, slot: BigUint, value: BigUint);
+    /// 删除账户及其存储
+    fn remove_account(&mut self, address: &Address);
+    /// 一笔交易的修改全部写入后调用，持久化的数据库在此原子地提交这批修改
+    fn commit(&mut self) {}
+}
+
+/// 可注入虚拟机的数据库
+pub trait StateDatabase: Database + DatabaseCommit + Debug {}
+
+impl<T: Database + DatabaseCommit + Debug> StateDatabase for T {}
+
+/// 虚拟机与子调用共享的数据库句柄
+pub type SharedDb = Rc<RefCell<dyn StateDatabase>>;
+
+/// 将数据库包装为可注入虚拟机的共享句柄
+pub fn shared<DB: StateDatabase + 'static>(db: DB) -> SharedDb {
+    Rc::new(RefCell::new(db))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::fake_db::AccountDb;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+
+    #[test]
+    fn test_keccak_empty() {
+        assert_eq!(B256::from(keccak256(&[])), KECCAK_EMPTY);
+        assert_eq!(AccountInfo::default().code_hash, KECCAK_EMPTY);
+    }
+
+    #[test]
+    fn test_account_db() {
+        let mut db = AccountDb::new();
+        let address = &"0x1000000000000000000000000000000000000c42"
+            .parse()
+            .unwrap();
+        assert_eq!(db.basic(address), None);
+        let code = hex::decode("60426000526001601ff3").unwrap();
+        db.insert_account(
+            address,
+            AccountInfo::new(BigUint::from(7u8), BigUint::from(1u8), code.clone()),
+        );
+        db.insert_storage(address, BigUint::from(1u8), BigUint::from(2u8));
+        let info = db.basic(address).unwrap();
+        assert_eq!(info.balance, BigUint::from(7u8));
+        assert_eq!(db.code_by_hash(&info.code_hash), code);
+        assert_eq!(db.storage(address, &BigUint::from(1u8)), BigUint::from(2u8));
+        assert_eq!(db.storage(address, &BigUint::from(2u8)), BigUint::from(0u8));
+        db.remove_account(address);
+        assert_eq!(db.basic(address), None);
+    }
+
+    #[test]
+    fn test_isolated_db() {
+        Lazy::force(&INIT_LOG);
+        // 向0x1000..0c42转账1 wei
+        let bytes =
+            hex::decode("6001601f5f5f6001731000000000000000000000000000000000000c425ff1").unwrap();
+        let db = shared(AccountDb::mock());
+        let mut evm_test = Evm::init_evm_with_db(bytes.clone(), Transaction::mock(), db.clone());
+        evm_test.run();
+        let balance = |db: &SharedDb| {
+            db.borrow_mut()
+                .basic(
+                    &"0x1000000000000000000000000000000000000c42"
+                        .parse()
+                        .unwrap(),
+                )
+                .unwrap()
+                .balance
+        };
+        assert_eq!(balance(&db), BigUint::from(1u8));
+
+        // 另一个数据库不受影响
+        let other = shared(AccountDb::mock());
+        assert_eq!(balance(&other), BigUint::from(0u8));
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        assert_eq!(balance(&db), BigUint::from(2u8));
+    }
+}
@@ -0,0 +1,264 @@
+/// gas估算
+/// 对应eth_estimateGas: 在状态快照上以不同的gas上限反复执行交易，
+/// 二分查找能让交易执行成功的最小gas上限。
+/// 由于子调用最多只能获得剩余gas的63/64，交易实际消耗的gas并不一定是足够的gas上限，
+/// 所以每一次尝试都需要真实执行而不能直接使用gas_used。
+/// 结果包含交易的固有gas（21000、交易数据和创建合约的费用），与eth_estimateGas一致。
+use crate::const_var::{
+    INIT_CODE_WORD_GAS, TX_BASE_GAS, TX_CREATE_GAS, TX_DATA_NON_ZERO_GAS, TX_DATA_ZERO_GAS,
+};
+use crate::db::{shared, StateDatabase};
+use crate::evm::Evm;
+use crate::transaction::Transaction;
+use log::*;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EstimateGasError {
+    /// 以交易自身的gas上限执行仍然失败（回滚或gas不足）
+    ExecutionFailed {
+        gas_limit: BigUint,
+        return_data: Vec<u8>,
+    },
+    /// 交易的gas上限不足以支付固有gas
+    IntrinsicGasTooLow {
+        gas_limit: BigUint,
+        intrinsic_gas: BigUint,
+    },
+}
+
+/// 交易的固有gas，在执行任何指令之前扣除
+/// ```
+/// use mini_evm::estimate_gas::intrinsic_gas;
+/// assert_eq!(intrinsic_gas(&[], false), 21000u32.into());
+/// assert_eq!(intrinsic_gas(&[0x00, 0x01], false), 21020u32.into());
+/// assert_eq!(intrinsic_gas(&[0x00], true), 53006u32.into());
+/// ```
+pub fn intrinsic_gas(data: &[u8], is_create: bool) -> BigUint {
+    let zeros = data.iter().filter(|&&byte| byte == 0).count() as u64;
+    let non_zeros = data.len() as u64 - zeros;
+    let mut gas = TX_BASE_GAS as u64
+        + zeros * TX_DATA_ZERO_GAS as u64
+        + non_zeros * TX_DATA_NON_ZERO_GAS as u64;
+    if is_create {
+        let words = (data.len() as u64).div_ceil(32);
+        gas += TX_CREATE_GAS as u64 + words * INIT_CODE_WORD_GAS as u64;
+    }
+    BigUint::from(gas)
+}
+
+/// 单次执行的结果
+struct Outcome {
+    success: bool,
+    gas_used: BigUint,
+    return_data: Vec<u8>,
+}
+
+/// 在状态快照上以给定的执行gas上限（不含固有gas）执行一次交易，不影响原数据库
+fn execute<DB>(code: &[u8], txn: &Transaction, db: &DB, gas_limit: &BigUint) -> Outcome
+where
+    DB: StateDatabase + Clone + 'static,
+{
+    let mut txn = txn.clone();
+    txn.set_gas_limit(gas_limit.clone());
+    let mut evm = Evm::init_evm_with_db(code.to_vec(), txn, shared(db.clone()));
+    evm.run();
+    Outcome {
+        success: evm.success,
+        gas_used: evm.gas_used,
+        return_data: evm.return_data,
+    }
+}
+
+/// 估算调用交易执行成功所需的最小gas上限
+/// code为目标账户的代码，交易自身的gas上限作为搜索的上界，每次尝试都在db的副本上执行
+/// ```
+/// use mini_evm::estimate_gas::estimate_gas;
+/// use mini_evm::fake_db::AccountDb;
+/// use mini_evm::transaction::Transaction;
+/// let bytes = hex::decode("6001600101").unwrap();
+/// let mut txn = Transaction::mock();
+/// txn.set_gas_limit(100000u32.into());
+/// let gas = estimate_gas(&bytes, &txn, &AccountDb::mock()).unwrap();
+/// assert_eq!(gas, 21009u32.into());
+/// ```
+pub fn estimate_gas<DB>(
+    code: &[u8],
+    txn: &Transaction,
+    db: &DB,
+) -> Result<BigUint, EstimateGasError>
+where
+    DB: StateDatabase + Clone + 'static,
+{
+    let intrinsic = intrinsic_gas(&txn.get_calldata(), false);
+    estimate(code, txn, db, intrinsic)
+}
+
+/// 估算创建合约的交易所需的最小gas上限，init_code即交易数据
+pub fn estimate_create_gas<DB>(
+    init_code: &[u8],
+    txn: &Transaction,
+    db: &DB,
+) -> Result<BigUint, EstimateGasError>
+where
+    DB: StateDatabase + Clone + 'static,
+{
+    let intrinsic = intrinsic_gas(init_code, true);
+    estimate(init_code, txn, db, intrinsic)
+}
+
+/// 二分查找执行所需的gas，返回值加上固有gas
+fn estimate<DB>(
+    code: &[u8],
+    txn: &Transaction,
+    db: &DB,
+    intrinsic: BigUint,
+) -> Result<BigUint, EstimateGasError>
+where
+    DB: StateDatabase + Clone + 'static,
+{
+    let gas_limit = txn.get_gas_limit().clone();
+    if gas_limit < intrinsic {
+        return Err(EstimateGasError::IntrinsicGasTooLow {
+            gas_limit,
+            intrinsic_gas: intrinsic,
+        });
+    }
+    let cap = &gas_limit - &intrinsic;
+    let outcome = execute(code, txn, db, &cap);
+    if !outcome.success {
+        return Err(EstimateGasError::ExecutionFailed {
+            gas_limit,
+            return_data: outcome.return_data,
+        });
+    }
+
+    // 没有消耗gas时只需要固有gas
+    if outcome.gas_used.is_zero() {
+        return Ok(intrinsic);
+    }
+    // 小于实际消耗的gas上限必然失败
+    let mut lo = outcome.gas_used - BigUint::one();
+    let mut hi = cap;
+    while &lo + BigUint::one() < hi {
+        let mid: BigUint = (&lo + &hi) >> 1;
+        if execute(code, txn, db, &mid).success {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    let gas = hi + intrinsic;
+    info!("estimate gas:{}", gas);
+    Ok(gas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::fake_db::AccountDb;
+    use num_traits::zero;
+    use once_cell::sync::Lazy;
+
+    /// gas上限为100000的模拟交易
+    fn mock_txn() -> Transaction {
+        let mut txn = Transaction::mock();
+        txn.set_gas_limit(BigUint::from(100000u32));
+        txn
+    }
+
+    #[test]
+    fn test_estimate_simple() {
+        Lazy::force(&INIT_LOG);
+        // PUSH1 PUSH1 ADD
+        let bytes = hex::decode("6001600101").unwrap();
+        let gas = estimate_gas(&bytes, &mock_txn(), &AccountDb::mock()).unwrap();
+        assert_eq!(gas, BigUint::from(21009u32));
+    }
+
+    #[test]
+    fn test_estimate_transfer() {
+        Lazy::force(&INIT_LOG);
+        // 向没有代码的账户转账只需要固有gas
+        let gas = estimate_gas(&[], &mock_txn(), &AccountDb::mock()).unwrap();
+        assert_eq!(gas, BigUint::from(21000u32));
+    }
+
+    #[test]
+    fn test_estimate_calldata() {
+        Lazy::force(&INIT_LOG);
+        // 交易数据00 01 ff: 4 + 16 + 16，执行CALLDATASIZE消耗2
+        let mut txn = mock_txn();
+        txn.set_data("0001ff".to_string());
+        let bytes = hex::decode("36").unwrap();
+        let gas = estimate_gas(&bytes, &txn, &AccountDb::mock()).unwrap();
+        assert_eq!(gas, BigUint::from(21000u32 + 36 + 2));
+
+        // 创建合约额外需要32000和每32字节2 gas的初始代码费用
+        let bytes = hex::decode("6001600101").unwrap();
+        let gas = estimate_create_gas(&bytes, &mock_txn(), &AccountDb::mock()).unwrap();
+        assert_eq!(gas, BigUint::from(21000u32 + 5 * 16 + 32000 + 2 + 9));
+    }
+
+    #[test]
+    fn test_estimate_out_of_gas() {
+        Lazy::force(&INIT_LOG);
+        let bytes = hex::decode("6001600101").unwrap();
+        let mut txn = Transaction::mock();
+        txn.set_gas_limit(BigUint::from(21008u32));
+        assert_eq!(
+            estimate_gas(&bytes, &txn, &AccountDb::mock()),
+            Err(EstimateGasError::ExecutionFailed {
+                gas_limit: BigUint::from(21008u32),
+                return_data: vec![],
+            })
+        );
+        txn.set_gas_limit(BigUint::from(20999u32));
+        assert_eq!(
+            estimate_gas(&bytes, &txn, &AccountDb::mock()),
+            Err(EstimateGasError::IntrinsicGasTooLow {
+                gas_limit: BigUint::from(20999u32),
+                intrinsic_gas: BigUint::from(21000u32),
+            })
+        );
+    }
+
+    #[test]
+    fn test_estimate_call() {
+        Lazy::force(&INIT_LOG);
+        // 以全部剩余gas调用0x1000..0c42，子调用受63/64规则限制
+        let bytes =
+            hex::decode("6001601f5f5f6001731000000000000000000000000000000000000c425af15f51")
+                .unwrap();
+        let txn = Transaction::init(
+            zero(),
+            BigUint::from(1u8),
+            BigUint::from(100000u32),
+            "0x1000000000000000000000000000000000000c42"
+                .parse()
+                .unwrap(),
+            zero(),
+            "".to_string(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            zero(),
+            zero(),
+            zero(),
+        );
+        let db = AccountDb::mock();
+        let gas = estimate_gas(&bytes, &txn, &db).unwrap();
+        // 交易数据为空，固有gas为21000
+        let execution_gas = &gas - BigUint::from(21000u32);
+        assert!(execute(&bytes, &txn, &db, &execution_gas).success);
+        assert!(!execute(&bytes, &txn, &db, &(&execution_gas - BigUint::one())).success);
+    }
+}
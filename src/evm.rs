@@ -1,10 +1,16 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 use crate::stack::Stack;
 use crate::stack::StackData;
 use crate::const_var::*;
 use crate::curr_block::*;
+use crate::db::*;
+use crate::fake_db::AccountDb;
+use crate::journal::*;
 use crate::log_entry::LogEntry;
 use crate::ops::traits::*;
+use crate::precompile::{Precompile, Precompiles};
+use crate::primitives::Address;
 use crate::transaction::*;
 use crate::utils::*;
 use hex::decode;
@@ -23,17 +29,21 @@ pub struct Evm {
     //堆栈
     //每个元素长度为256位（32字节），最大深度为1024元素，但是每个操作只能操作堆栈顶的16个元素
     pub stack: Stack,
-    //存储
-    pub storage: HashMap<BigUint, (BigUint, u8)>,
     //内存
     pub memory: Vec<u8>,
     // 有效指令
     pub valid_jumpdest: HashMap<usize, bool>,
 
-    pub current_block: CurrentBlock,
+    pub current_block: BlockEnv,
 
     pub txn: Transaction,
 
+    // 带日志的世界状态，与子调用共享
+    pub state: SharedState,
+
+    // 调用深度，0为交易的顶层调用
+    pub depth: usize,
+
     pub logs: Vec<LogEntry>,
 
     pub return_data: Vec<u8>,
@@ -43,6 +53,9 @@ pub struct Evm {
     pub is_static: bool,
 
     pub gas_used: BigUint,
+
+    // 预编译合约表，与子调用共享
+    pub precompiles: Rc<Precompiles>,
 }
 
 /// 为虚拟机实现其特征行为和方法
@@ -58,40 +71,7 @@ impl Evm {
     /// let mut evm_test = Evm::new(bytes);
     /// ```
     pub fn new(code: Vec<u8>) -> Self {
-        // 初始化valid_jumpdest
-        // let mut vaild_jumpdest: HashMap<usize, bool> = HashMap::new();
-        let valid_jumpdest = code
-            .iter()
-            .fold(
-                (HashMap::<usize, bool>::new(), 0 as usize),
-                |(mut mp, idx), &val| {
-                    let mut step = 1 as usize;
-                    if val == JUMPDEST {
-                        mp.insert(idx, true);
-                    }
-                    if PUSH1 <= val && val <= PUSH32 {
-                        step = (val - PUSH1 + 1) as usize;
-                    }
-                    (mp, idx + step)
-                },
-            )
-            .0;
-
-        Evm {
-            code: code,
-            pc: 0,
-            stack: Stack::new(),
-            memory: Vec::<u8>::new(),
-            storage: HashMap::new(),
-            valid_jumpdest: valid_jumpdest,
-            current_block: CurrentBlock::init(),
-            txn: Transaction::mock(),
-            logs: Vec::<LogEntry>::new(),
-            return_data: Vec::<u8>::new(),
-            success: true,
-            is_static: true,
-            gas_used: zero(),
-        }
+        Self::init_evm(code, Transaction::mock())
     }
     /// 初始化虚拟机并设置上下文txn
     /// 后续将new替换成init_evm
@@ -101,6 +81,26 @@ impl Evm {
     /// let mut evm_test = Evm::new(bytes);
     /// ```
     pub fn init_evm(code: Vec<u8>, txn: Transaction) -> Self {
+        Self::init_evm_with_db(code, txn, shared(AccountDb::mock()))
+    }
+    /// 初始化虚拟机并注入世界状态数据库
+    /// 子调用与父调用共享同一个数据库
+    /// ```
+    /// use mini_evm::db::shared;
+    /// use mini_evm::evm::Evm;
+    /// use mini_evm::fake_db::AccountDb;
+    /// use mini_evm::transaction::Transaction;
+    /// let bytes = vec![0x60, 0x01, 0x60, 0x01, 0x50];
+    /// let db = shared(AccountDb::new());
+    /// let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+    /// evm_test.run();
+    /// ```
+    pub fn init_evm_with_db(code: Vec<u8>, txn: Transaction, db: SharedDb) -> Self {
+        Self::init_evm_with_state(code, txn, JournaledState::shared(db))
+    }
+    /// 初始化虚拟机并指定世界状态
+    /// 顶层调用执行结束时会将状态写回数据库
+    pub fn init_evm_with_state(code: Vec<u8>, txn: Transaction, state: SharedState) -> Self {
         // init_log();
 
         // 初始化valid_jumpdest
@@ -127,23 +127,76 @@ impl Evm {
             pc: 0,
             stack: Stack::new(),
             memory: Vec::<u8>::new(),
-            storage: HashMap::new(),
             valid_jumpdest: valid_jumpdest,
-            current_block: CurrentBlock::init(),
+            current_block: BlockEnv::init(),
             txn: txn,
+            state,
+            depth: 0,
             logs: Vec::<LogEntry>::new(),
             return_data: Vec::<u8>::new(),
             success: true,
-            is_static: true,
+            is_static: false,
             gas_used: zero(),
+            precompiles: Rc::new(Precompiles::latest()),
+        }
+    }
+    /// 创建共享世界状态的子调用虚拟机
+    pub fn sub_evm(&self, code: Vec<u8>, mut txn: Transaction) -> Self {
+        txn.set_blob_hashes(self.txn.get_blob_hashes().to_vec());
+        let mut evm_sub = Self::init_evm_with_state(code, txn, self.state.clone());
+        evm_sub.depth = self.depth + 1;
+        evm_sub.current_block = self.current_block.clone();
+        // 静态调用中的所有子调用同样是静态的
+        evm_sub.is_static = self.is_static;
+        evm_sub.precompiles = self.precompiles.clone();
+        evm_sub
+    }
+    /// 执行子调用，目标地址为预编译合约时直接执行预编译，否则执行该地址的代码
+    pub fn run_sub_call(&self, code_address: &Address, txn: Transaction, is_static: bool) -> Self {
+        let precompile = self.precompiles.get(code_address);
+        let code = match precompile {
+            Some(_) => Vec::new(),
+            None => self.account_code(code_address),
+        };
+        let mut evm_sub = self.sub_evm(code, txn);
+        evm_sub.is_static |= is_static;
+        match precompile {
+            Some(precompile) => evm_sub.run_precompile(precompile),
+            None => evm_sub.run(),
+        }
+        evm_sub
+    }
+    /// 以交易数据为输入执行预编译合约，失败时消耗全部gas
+    fn run_precompile(&mut self, precompile: Precompile) {
+        let input = self.txn.get_calldata();
+        let gas_limit = self.txn.get_gas_limit().to_u64().unwrap_or(u64::MAX);
+        match precompile(&input, gas_limit) {
+            Ok(result) => {
+                self.gas_used = BigUint::from(result.gas_used);
+                self.return_data = result.output;
+            }
+            Err(err) => {
+                info!("precompile failed: {}", err);
+                self.success = false;
+                self.return_data = Vec::new();
+                self.gas_used = self.txn.get_gas_limit().clone();
+            }
         }
     }
+    /// 注册预编译合约，子调用同样可以调用
+    pub fn register_precompile(&mut self, address: Address, precompile: Precompile) {
+        Rc::make_mut(&mut self.precompiles).register(address, precompile);
+    }
     /// 合约间调用，用于上一组指令执行完后，保留返回的结果并执行下一组指令
     /// 仅用于returncopy的测试
     pub fn next_codes(&mut self, code: Vec<u8>) {
         self.code = code;
         self.pc = 0;
     }
+    /// 当前帧正在执行的字节码
+    pub fn get_code(&self) -> &[u8] {
+        &self.code
+    }
 
     /// 获取当前待执行的指令
     /// ```
@@ -165,7 +218,7 @@ impl Evm {
         return op.clone();
     }
     pub fn is_state_change_code(&mut self, code: u8) -> bool {
-        [0xf0, 0xf5, 0xff, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0x55].contains(&code)
+        [0xf0, 0xf5, 0xff, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0x55, 0x5d].contains(&code)
     }
     /// 执行所有指令
     /// ```
@@ -175,6 +228,21 @@ impl Evm {
     /// evm_test.run();
     /// ```
     pub fn run(&mut self) {
+        let checkpoint = self.state.borrow_mut().checkpoint();
+        self.execute();
+        // 顶层调用即整个交易，失败时回滚交易内的全部状态修改，结束时写回数据库
+        // 子调用的回滚由调用方在调用边界处理
+        if self.depth == 0 {
+            let mut state = self.state.borrow_mut();
+            if !self.success {
+                state.checkpoint_revert(checkpoint);
+            }
+            self.logs = state.finalize();
+        }
+    }
+
+    /// 逐条执行指令直到停止
+    fn execute(&mut self) {
         while self.pc < self.code.len() {
             let op: u8 = self.get_current_instruction();
             if GAS_COSTS.contains_key(&op) {
@@ -185,6 +253,15 @@ impl Evm {
                 );
                 self.gas_used += BigUint::from(GAS_COSTS.get(&op).unwrap().clone());
             }
+            // 静态gas不足时直接停止，不再执行该指令
+            if self.is_out_of_gas() {
+                self.halt_out_of_gas();
+                break;
+            }
+            if self.is_static_violation(op) {
+                self.halt_static_violation();
+                break;
+            }
             match op {
                 op if (PUSH1 <= op && op <= PUSH32) => {
                     let size = (op - PUSH1 + 1) as usize;
@@ -289,6 +366,9 @@ impl Evm {
                 MSIZE => {
                     self.msize();
                 }
+                MCOPY => {
+                    self.mcopy();
+                }
                 MLOAD => {
                     self.mload();
                 }
@@ -298,6 +378,12 @@ impl Evm {
                 SLOAD => {
                     self.sload();
                 }
+                TSTORE => {
+                    self.tstore();
+                }
+                TLOAD => {
+                    self.tload();
+                }
                 STOP => {
                     info!("stop");
                     break;
@@ -338,6 +424,12 @@ impl Evm {
                 SELFBALANCE => {
                     self.selfbalance();
                 }
+                BLOBHASH => {
+                    self.blobhash();
+                }
+                BLOBBASEFEE => {
+                    self.blobbasefee();
+                }
                 BASEFEE => {
                     self.basefee();
                 }
@@ -392,6 +484,7 @@ impl Evm {
                 }
                 RETURN => {
                     self.return_fn();
+                    break;
                 }
                 RETURNDATASIZE => {
                     self.return_datasize();
@@ -401,21 +494,22 @@ impl Evm {
                 }
                 REVERT => {
                     self.revert();
+                    break;
                 }
                 INVALID => {
                     self.invalid();
+                    break;
                 }
                 CALL => {
                     self.call();
                 }
+                CALLCODE => {
+                    self.callcode();
+                }
                 DELEGATECALL => {
                     self.delegatecall();
                 }
                 STATICCALL => {
-                    if self.is_state_change_code(op.clone()) && self.is_static {
-                        self.success = false;
-                        panic!("State changing operation detected during STATICCALL!");
-                    }
                     self.staticcall();
                 }
                 CREATE => {
@@ -426,6 +520,7 @@ impl Evm {
                 }
                 SELFDESTRUCT => {
                     self.selfdestruct();
+                    break;
                 }
                 GAS => {
                     self.gas();
@@ -434,14 +529,73 @@ impl Evm {
                     // 处理其他未覆盖到的操作
                 }
             }
-            if &self.gas_used > self.txn.get_gas_limit() {
-                info!("gas_used:{}", self.gas_used);
-                info!("gas_limit:{}", self.txn.get_gas_limit());
-                panic!("Out of gas!");
+            // 子调用等动态gas消耗后再次检查
+            if self.is_out_of_gas() {
+                self.halt_out_of_gas();
+                break;
             }
         }
     }
 
+    /// 已消耗的gas是否超过交易的gas上限
+    pub fn is_out_of_gas(&self) -> bool {
+        &self.gas_used > self.txn.get_gas_limit()
+    }
+
+    /// gas耗尽，执行失败并消耗掉全部gas
+    fn halt_out_of_gas(&mut self) {
+        info!("gas_used:{}", self.gas_used);
+        info!("gas_limit:{}", self.txn.get_gas_limit());
+        info!("Out of gas!");
+        self.success = false;
+        self.return_data = Vec::new();
+        self.gas_used = self.txn.get_gas_limit().clone();
+    }
+
+    /// 静态调用中是否执行了修改状态的指令，带value的CALL同样视为修改状态
+    fn is_static_violation(&mut self, op: u8) -> bool {
+        if !self.is_static {
+            return false;
+        }
+        if op == CALL && self.stack.len() >= 3 {
+            return self.stack.get(3).data.iter().any(|&byte| byte != 0);
+        }
+        self.is_state_change_code(op)
+    }
+
+    /// 静态调用中修改状态，执行失败并消耗掉全部gas
+    fn halt_static_violation(&mut self) {
+        info!("State changing operation detected during STATICCALL!");
+        self.success = false;
+        self.return_data = Vec::new();
+        self.gas_used = self.txn.get_gas_limit().clone();
+    }
+
+    /// 读取账户的合约代码，账户不存在时为空
+    pub fn account_code(&self, address: &Address) -> Vec<u8> {
+        self.state.borrow_mut().code(address)
+    }
+
+    /// 剩余可用gas
+    pub fn remaining_gas(&self) -> BigUint {
+        if self.is_out_of_gas() {
+            return zero();
+        }
+        self.txn.get_gas_limit() - &self.gas_used
+    }
+
+    /// 子调用可获得的gas上限
+    /// EIP-150: 最多只能转交剩余gas的63/64，调用者至少保留1/64
+    pub fn sub_call_gas_limit(&self, requested: &BigUint) -> BigUint {
+        let remaining = self.remaining_gas();
+        let max_gas = &remaining - &remaining / BigUint::from(64u8);
+        if requested < &max_gas {
+            requested.clone()
+        } else {
+            max_gas
+        }
+    }
+
     /// 堆栈行为
     /// 出栈
     /// ```
@@ -523,6 +677,20 @@ impl Evm {
         let len = self.stack.len();
         self.stack.swap(len, index);
     }
+    /// 将内存扩展到至少end字节（按32字节对齐），并计入扩展的gas
+    /// 内存成本为 3 * 字数 + 字数^2 / 512，扩展时只收取新旧成本之差
+    pub fn expand_memory(&mut self, end: usize) {
+        if end <= self.memory.len() {
+            return;
+        }
+        let memory_cost = |len: usize| {
+            let words = BigUint::from(len.div_ceil(32));
+            &words * 3u8 + &words * &words / 512u32
+        };
+        self.gas_used += memory_cost(end) - memory_cost(self.memory.len());
+        self.memory.resize(end.div_ceil(32) * 32, 0);
+    }
+
     pub fn fill_memory(&mut self) {
         // 获取当前内存长度
         let current_len = self.memory.len();
@@ -1,23 +1,35 @@
 use num_bigint::BigUint;
-use once_cell::sync::Lazy;
-use std::{collections::HashMap, sync::Mutex};
+use num_traits::Zero;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+use crate::db::{AccountInfo, Database, DatabaseCommit, KECCAK_EMPTY};
+use crate::genesis::Genesis;
+use crate::primitives::{Address, B256};
+use crate::proof::{storage_key, AccountProof, StorageProof};
+use crate::trie::{state_trie, storage_trie, MerklePatriciaTrie, TrieAccount};
+use crate::utils::keccak256;
+
+#[derive(Debug, Clone, Default)]
 pub struct Account {
     pub balance: BigUint,
     pub nonce: BigUint,
-    pub storage: HashMap<BigUint, (BigUint, u8)>,
+    pub storage: HashMap<BigUint, BigUint>,
     pub code: Vec<u8>,
 }
-#[derive(Debug, Clone)]
+/// 内存账户数据库
+#[derive(Debug, Clone, Default)]
 pub struct AccountDb {
-    data: HashMap<String, Account>,
+    data: HashMap<Address, Account>,
+    // 代码哈希 -> 合约代码
+    contracts: HashMap<B256, Vec<u8>>,
+    // 区块号 -> 区块哈希
+    block_hashes: HashMap<BigUint, B256>,
 }
 impl Account {
     pub fn new(
         balance: BigUint,
         nonce: BigUint,
-        storage: HashMap<BigUint, (BigUint, u8)>,
+        storage: HashMap<BigUint, BigUint>,
         code: Vec<u8>,
     ) -> Self {
         Self {
@@ -29,67 +41,145 @@ impl Account {
     }
 }
 impl AccountDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 测试用的初始状态，账户定义在fixtures/mock_genesis.json中
     pub fn mock() -> Self {
-        let account = Account {
-            balance: BigUint::from(100u8),
-            nonce: BigUint::from(1u8),
-            storage: HashMap::new(),
-            code: hex::decode("60006000").unwrap(),
-        };
-        let account2 = Account {
-            balance: BigUint::from(100u8),
-            nonce: BigUint::from(1u8),
-            storage: HashMap::new(),
-            code: hex::decode("60006000").unwrap(),
-        };
-        let mut accounts: HashMap<String, Account> = HashMap::new();
-        accounts.insert(
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
-            account,
-        );
-        accounts.insert(
-            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            account2,
-        );
-        Self { data: accounts }
-    }
-    pub fn mock_2() -> Self {
-        let account_1 = Account {
-            balance: BigUint::from(100u8),
-            nonce: BigUint::from(1u8),
-            storage: HashMap::new(),
-            code: hex::decode("").unwrap(),
-        };
-        let account_2 = Account {
-            balance: BigUint::from(0u8),
-            nonce: BigUint::from(0u8),
-            storage: HashMap::new(),
-            code: hex::decode("60426000526001601ff3").unwrap(),
-        };
-        let mut accounts: HashMap<String, Account> = HashMap::new();
-        accounts.insert(
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
-            account_1,
-        );
-        accounts.insert(
-            "0x1000000000000000000000000000000000000c42".to_string(),
-            account_2,
-        );
-        Self { data: accounts }
-    }
-    pub fn get_account(&self, address: String) -> &Account {
+        Genesis::from_json(include_str!("../fixtures/mock_genesis.json"))
+            .expect("invalid mock genesis")
+            .to_db()
+    }
+    pub fn get_account(&self, address: Address) -> &Account {
         self.data.get(&address).unwrap()
     }
-    pub fn get_account_mut(&mut self, address: String) -> &mut Account {
+    pub fn get_account_mut(&mut self, address: Address) -> &mut Account {
         self.data.get_mut(&address).unwrap()
     }
-    pub fn insert(&mut self, address: String, account: Account) {
+    pub fn insert(&mut self, address: Address, account: Account) {
+        self.contracts
+            .insert(B256::from(keccak256(&account.code)), account.code.clone());
         self.data.insert(address, account);
     }
-    pub fn contains(&mut self, address: String) -> bool {
+    /// 遍历所有账户
+    pub fn accounts(&self) -> impl Iterator<Item = (&Address, &Account)> {
+        self.data.iter()
+    }
+    pub fn contains(&mut self, address: Address) -> bool {
         self.data.contains_key(&address)
     }
-    pub fn remove(&mut self, address: String) {
+    pub fn remove(&mut self, address: Address) {
         self.data.remove(&address);
     }
+    /// 账户存储树的根哈希，账户不存在时为空树的根哈希
+    pub fn storage_root(&self, address: &Address) -> B256 {
+        self.data
+            .get(address)
+            .map(|account| storage_trie(&account.storage))
+            .unwrap_or_default()
+            .root_hash()
+    }
+    /// 世界状态树
+    pub fn state_trie(&self) -> MerklePatriciaTrie {
+        state_trie(self.data.iter().map(|(address, account)| {
+            (
+                address,
+                TrieAccount {
+                    nonce: account.nonce.clone(),
+                    balance: account.balance.clone(),
+                    storage_root: storage_trie(&account.storage).root_hash(),
+                    code_hash: B256::from(keccak256(&account.code)),
+                },
+            )
+        }))
+    }
+    /// 世界状态树的根哈希
+    pub fn state_root(&self) -> B256 {
+        self.state_trie().root_hash()
+    }
+    /// 生成账户及指定存储槽的Merkle证明，对应eth_getProof
+    pub fn get_proof(&self, address: &Address, slots: &[BigUint]) -> AccountProof {
+        let account_proof = self.state_trie().get_proof(&keccak256(address.as_bytes()));
+        let account = self.data.get(address).cloned().unwrap_or_default();
+        let storage = storage_trie(&account.storage);
+        let storage_proof = slots
+            .iter()
+            .map(|slot| StorageProof {
+                key: slot.clone(),
+                value: account.storage.get(slot).cloned().unwrap_or_default(),
+                proof: storage.get_proof(&storage_key(slot)),
+            })
+            .collect();
+        let code_hash = if self.data.contains_key(address) {
+            B256::from(keccak256(&account.code))
+        } else {
+            KECCAK_EMPTY
+        };
+        AccountProof {
+            address: *address,
+            balance: account.balance,
+            code_hash,
+            nonce: account.nonce,
+            storage_hash: storage.root_hash(),
+            account_proof,
+            storage_proof,
+        }
+    }
+    pub fn insert_block_hash(&mut self, number: BigUint, hash: B256) {
+        self.block_hashes.insert(number, hash);
+    }
+    /// 遍历所有区块哈希
+    pub fn block_hashes(&self) -> impl Iterator<Item = (&BigUint, &B256)> {
+        self.block_hashes.iter()
+    }
+}
+
+impl Database for AccountDb {
+    fn basic(&mut self, address: &Address) -> Option<AccountInfo> {
+        self.data.get(address).map(|account| {
+            AccountInfo::new(
+                account.balance.clone(),
+                account.nonce.clone(),
+                account.code.clone(),
+            )
+        })
+    }
+    fn code_by_hash(&mut self, code_hash: &B256) -> Vec<u8> {
+        self.contracts.get(code_hash).cloned().unwrap_or_default()
+    }
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> BigUint {
+        self.data
+            .get(address)
+            .and_then(|account| account.storage.get(slot))
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn block_hash(&mut self, number: &BigUint) -> B256 {
+        self.block_hashes.get(number).cloned().unwrap_or_default()
+    }
+}
+
+impl DatabaseCommit for AccountDb {
+    fn insert_account(&mut self, address: &Address, info: AccountInfo) {
+        let code = match info.code {
+            Some(code) => code,
+            None => self.code_by_hash(&info.code_hash),
+        };
+        self.contracts.insert(info.code_hash, code.clone());
+        let account = self.data.entry(*address).or_default();
+        account.balance = info.balance;
+        account.nonce = info.nonce;
+        account.code = code;
+    }
+    fn insert_storage(&mut self, address: &Address, slot: BigUint, value: BigUint) {
+        let account = self.data.entry(*address).or_default();
+        if value.is_zero() {
+            account.storage.remove(&slot);
+        } else {
+            account.storage.insert(slot, value);
+        }
+    }
+    fn remove_account(&mut self, address: &Address) {
+        self.data.remove(address);
+    }
 }
@@ -0,0 +1,440 @@
+/// 持久化到本地目录的状态数据库
+/// 目录中包含状态快照state.snapshot（state_dump的二进制格式）和追加写入的修改日志state.log。
+/// 日志中每个条目是一次提交（一笔交易）的全部修改，以单个RLP列表写入，
+/// 打开时先加载快照再重放日志，末尾不完整的条目整体丢弃，因此不会只恢复一笔交易的部分修改；
+/// 日志条目数达到阈值时压缩，即写入新快照并清空日志。
+/// 内存中保存一份完整的AccountDb用于读取。
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::*;
+use num_bigint::BigUint;
+
+use crate::db::{AccountInfo, Database, DatabaseCommit};
+use crate::fake_db::AccountDb;
+use crate::primitives::{Address, B256};
+use crate::rlp::{encode_list_payload, Decodable, Encodable, RlpError, RlpItem};
+use crate::state_dump::DumpError;
+
+const SNAPSHOT_FILE: &str = "state.snapshot";
+const LOG_FILE: &str = "state.log";
+/// 默认的日志压缩阈值
+const DEFAULT_COMPACTION_THRESHOLD: usize = 1000;
+
+/// 日志中的一条状态修改
+#[derive(Debug, Clone, PartialEq)]
+enum LogRecord {
+    InsertAccount {
+        address: Address,
+        balance: BigUint,
+        nonce: BigUint,
+        code: Vec<u8>,
+    },
+    InsertStorage {
+        address: Address,
+        slot: BigUint,
+        value: BigUint,
+    },
+    RemoveAccount {
+        address: Address,
+    },
+}
+
+impl LogRecord {
+    /// 编码为 [类型, 字段...]
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            LogRecord::InsertAccount {
+                address,
+                balance,
+                nonce,
+                code,
+            } => {
+                0u64.rlp_append(&mut payload);
+                address.rlp_append(&mut payload);
+                balance.rlp_append(&mut payload);
+                nonce.rlp_append(&mut payload);
+                code.rlp_append(&mut payload);
+            }
+            LogRecord::InsertStorage {
+                address,
+                slot,
+                value,
+            } => {
+                1u64.rlp_append(&mut payload);
+                address.rlp_append(&mut payload);
+                slot.rlp_append(&mut payload);
+                value.rlp_append(&mut payload);
+            }
+            LogRecord::RemoveAccount { address } => {
+                2u64.rlp_append(&mut payload);
+                address.rlp_append(&mut payload);
+            }
+        }
+        let mut out = Vec::new();
+        encode_list_payload(&payload, &mut out);
+        out
+    }
+
+    fn decode(item: &RlpItem) -> Result<Self, RlpError> {
+        let items = item.as_list()?;
+        let tag = u64::rlp_decode(items.first().ok_or(RlpError::UnexpectedEof)?)?;
+        let expected = match tag {
+            0 => 5,
+            1 => 4,
+            _ => 2,
+        };
+        let items = item.as_list_of(expected)?;
+        let address = Address::rlp_decode(&items[1])?;
+        match tag {
+            0 => Ok(LogRecord::InsertAccount {
+                address,
+                balance: BigUint::rlp_decode(&items[2])?,
+                nonce: BigUint::rlp_decode(&items[3])?,
+                code: Vec::<u8>::rlp_decode(&items[4])?,
+            }),
+            1 => Ok(LogRecord::InsertStorage {
+                address,
+                slot: BigUint::rlp_decode(&items[2])?,
+                value: BigUint::rlp_decode(&items[3])?,
+            }),
+            2 => Ok(LogRecord::RemoveAccount { address }),
+            _ => Err(RlpError::InvalidInteger),
+        }
+    }
+
+    /// 一次提交的日志条目：[记录...]
+    fn encode_batch(records: &[LogRecord]) -> Vec<u8> {
+        let payload: Vec<u8> = records.iter().flat_map(LogRecord::encode).collect();
+        let mut out = Vec::new();
+        encode_list_payload(&payload, &mut out);
+        out
+    }
+
+    fn decode_batch(item: &RlpItem) -> Result<Vec<Self>, RlpError> {
+        item.as_list()?.iter().map(LogRecord::decode).collect()
+    }
+
+    fn apply(self, db: &mut AccountDb) {
+        match self {
+            LogRecord::InsertAccount {
+                address,
+                balance,
+                nonce,
+                code,
+            } => db.insert_account(&address, AccountInfo::new(balance, nonce, code)),
+            LogRecord::InsertStorage {
+                address,
+                slot,
+                value,
+            } => db.insert_storage(&address, slot, value),
+            LogRecord::RemoveAccount { address } => db.remove_account(&address),
+        }
+    }
+}
+
+/// 文件持久化的状态数据库
+/// 写入先进入内存并缓存在待提交列表中，commit时作为一个日志条目追加到日志。
+/// DatabaseCommit的接口不能返回错误，写日志或压缩失败时直接panic，
+/// 此时内存状态已包含本次修改而磁盘上没有，数据库不可继续使用；
+/// 需要自行处理I/O错误时可以调用try_commit。
+/// ```
+/// use mini_evm::db::{shared, Database};
+/// use mini_evm::evm::Evm;
+/// use mini_evm::file_db::FileDb;
+/// use mini_evm::transaction::Transaction;
+/// let dir = tempfile::tempdir().unwrap();
+/// let db = shared(FileDb::open(dir.path()).unwrap());
+/// let mut evm_test = Evm::init_evm_with_db(hex::decode("60f1600155").unwrap(), Transaction::mock(), db);
+/// evm_test.run();
+/// drop(evm_test);
+/// // 重新打开后存储仍然存在
+/// let mut db = FileDb::open(dir.path()).unwrap();
+/// let address = Transaction::mock().get_this_addr();
+/// assert_eq!(db.storage(&address, &1u8.into()), 0xf1u8.into());
+/// ```
+#[derive(Debug)]
+pub struct FileDb {
+    dir: PathBuf,
+    db: AccountDb,
+    log: File,
+    // 已应用到内存但尚未写入日志的修改
+    pending: Vec<LogRecord>,
+    // 上次压缩后写入的日志条目数
+    log_records: usize,
+    compaction_threshold: usize,
+}
+
+impl FileDb {
+    /// 打开目录中的数据库，目录不存在时创建空数据库
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, DumpError> {
+        Self::open_with(dir, AccountDb::new())
+    }
+
+    /// 打开目录中的数据库，目录中没有快照时以initial作为初始状态
+    pub fn open_with(dir: impl AsRef<Path>, initial: AccountDb) -> Result<Self, DumpError> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let snapshot = dir.join(SNAPSHOT_FILE);
+        let has_snapshot = snapshot.exists();
+        let mut db = if has_snapshot {
+            AccountDb::load(&snapshot)?
+        } else {
+            initial
+        };
+
+        // 重放日志，末尾不完整的条目（写入时进程中断）被整体丢弃
+        let log_path = dir.join(LOG_FILE);
+        let bytes = if log_path.exists() {
+            std::fs::read(&log_path)?
+        } else {
+            Vec::new()
+        };
+        let mut offset = 0;
+        let mut log_records = 0;
+        while offset < bytes.len() {
+            let (item, consumed) = match RlpItem::decode_prefix(&bytes[offset..]) {
+                Ok(decoded) => decoded,
+                Err(RlpError::UnexpectedEof) => {
+                    warn!("丢弃状态日志末尾{}字节不完整的条目", bytes.len() - offset);
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            for record in LogRecord::decode_batch(&item)? {
+                record.apply(&mut db);
+            }
+            offset += consumed;
+            log_records += 1;
+        }
+
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        log.set_len(offset as u64)?;
+        let mut file_db = Self {
+            dir,
+            db,
+            log,
+            pending: Vec::new(),
+            log_records,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        };
+        if !has_snapshot {
+            file_db.compact()?;
+        }
+        Ok(file_db)
+    }
+
+    /// 设置日志压缩阈值
+    pub fn with_compaction_threshold(mut self, threshold: usize) -> Self {
+        self.compaction_threshold = threshold;
+        self
+    }
+
+    /// 内存中的当前状态
+    pub fn state(&self) -> &AccountDb {
+        &self.db
+    }
+
+    /// 写入新快照并清空日志，快照先写入临时文件再重命名，保证快照文件始终完整
+    /// 快照包含内存中的全部状态，待提交的修改也随之持久化
+    pub fn compact(&mut self) -> Result<(), DumpError> {
+        let snapshot = self.dir.join(SNAPSHOT_FILE);
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&self.db.dump_binary())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &snapshot)?;
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        self.pending.clear();
+        self.log_records = 0;
+        info!("状态快照已写入{:?}", snapshot);
+        Ok(())
+    }
+
+    /// 将日志写入磁盘
+    pub fn sync(&self) -> Result<(), DumpError> {
+        self.log.sync_data()?;
+        Ok(())
+    }
+
+    /// 将待提交的修改作为一个日志条目写入，日志条目数达到阈值时压缩
+    pub fn try_commit(&mut self) -> Result<(), DumpError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.log
+            .write_all(&LogRecord::encode_batch(&self.pending))?;
+        self.pending.clear();
+        self.log_records += 1;
+        if self.log_records >= self.compaction_threshold {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, record: LogRecord) {
+        record.clone().apply(&mut self.db);
+        self.pending.push(record);
+    }
+}
+
+impl Database for FileDb {
+    fn basic(&mut self, address: &Address) -> Option<AccountInfo> {
+        self.db.basic(address)
+    }
+    fn code_by_hash(&mut self, code_hash: &B256) -> Vec<u8> {
+        self.db.code_by_hash(code_hash)
+    }
+    fn storage(&mut self, address: &Address, slot: &BigUint) -> BigUint {
+        self.db.storage(address, slot)
+    }
+    fn block_hash(&mut self, number: &BigUint) -> B256 {
+        self.db.block_hash(number)
+    }
+}
+
+impl DatabaseCommit for FileDb {
+    fn insert_account(&mut self, address: &Address, info: AccountInfo) {
+        let code = match info.code {
+            Some(code) => code,
+            None => self.db.code_by_hash(&info.code_hash),
+        };
+        self.append(LogRecord::InsertAccount {
+            address: *address,
+            balance: info.balance,
+            nonce: info.nonce,
+            code,
+        });
+    }
+    fn insert_storage(&mut self, address: &Address, slot: BigUint, value: BigUint) {
+        self.append(LogRecord::InsertStorage {
+            address: *address,
+            slot,
+            value,
+        });
+    }
+    fn remove_account(&mut self, address: &Address) {
+        self.append(LogRecord::RemoveAccount { address: *address });
+    }
+    fn commit(&mut self) {
+        self.try_commit().expect("failed to write state log");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::shared;
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+
+    /// 在目录中的数据库上执行一笔交易
+    fn run(dir: &Path, code: &str) {
+        let db = FileDb::open_with(dir, AccountDb::mock()).unwrap();
+        let mut evm_test =
+            Evm::init_evm_with_db(hex::decode(code).unwrap(), Transaction::mock(), shared(db));
+        evm_test.run();
+        assert!(evm_test.success);
+    }
+
+    #[test]
+    fn test_reopen() {
+        Lazy::force(&INIT_LOG);
+        let dir = tempfile::tempdir().unwrap();
+        // 两次调用计数器逻辑 slot0 = slot0 + 1
+        run(dir.path(), "600054600101600055");
+        run(dir.path(), "600054600101600055");
+        let db = FileDb::open(dir.path()).unwrap();
+        let mut expected = AccountDb::mock();
+        let address = Transaction::mock().get_this_addr();
+        expected.insert_storage(&address, BigUint::from(0u8), BigUint::from(2u8));
+        assert_eq!(db.state().state_root(), expected.state_root());
+    }
+
+    #[test]
+    fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let address: Address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let mut db = FileDb::open(dir.path())
+            .unwrap()
+            .with_compaction_threshold(3);
+        for i in 0..5u32 {
+            db.insert_storage(&address, BigUint::from(i), BigUint::from(i + 1));
+            db.commit();
+        }
+        // 3次提交后压缩，日志中只剩2个条目
+        let log_len = std::fs::metadata(dir.path().join(LOG_FILE)).unwrap().len();
+        assert!(log_len > 0);
+        assert_eq!(db.log_records, 2);
+        let root = db.state().state_root();
+        drop(db);
+        let mut db = FileDb::open(dir.path()).unwrap();
+        assert_eq!(db.state().state_root(), root);
+        assert_eq!(
+            db.storage(&address, &BigUint::from(4u8)),
+            BigUint::from(5u8)
+        );
+    }
+
+    #[test]
+    fn test_torn_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let address: Address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let mut db = FileDb::open(dir.path()).unwrap();
+        db.insert_account(
+            &address,
+            AccountInfo::new(BigUint::from(7u8), BigUint::from(1u8), vec![0x00]),
+        );
+        db.insert_storage(&address, BigUint::from(1u8), BigUint::from(1u8));
+        db.commit();
+        // 第二次提交包含多条记录
+        db.remove_account(&address);
+        db.insert_storage(&address, BigUint::from(2u8), BigUint::from(2u8));
+        db.commit();
+        // 未提交的修改不会写入日志
+        db.insert_storage(&address, BigUint::from(3u8), BigUint::from(3u8));
+        drop(db);
+        // 模拟写入第二次提交的最后一个字节时中断
+        let log_path = dir.path().join(LOG_FILE);
+        let len = std::fs::metadata(&log_path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&log_path).unwrap();
+        file.set_len(len - 1).unwrap();
+        drop(file);
+
+        // 第二次提交被整体丢弃，账户没有被删除一半
+        let mut db = FileDb::open(dir.path()).unwrap();
+        assert_eq!(db.basic(&address).unwrap().balance, BigUint::from(7u8));
+        assert_eq!(
+            db.storage(&address, &BigUint::from(1u8)),
+            BigUint::from(1u8)
+        );
+        assert_eq!(
+            db.storage(&address, &BigUint::from(2u8)),
+            BigUint::from(0u8)
+        );
+        assert_eq!(
+            db.storage(&address, &BigUint::from(3u8)),
+            BigUint::from(0u8)
+        );
+        // 不完整的条目已被截掉，之后的提交可以正常重放
+        db.insert_storage(&address, BigUint::from(4u8), BigUint::from(4u8));
+        db.commit();
+        drop(db);
+        let mut db = FileDb::open(dir.path()).unwrap();
+        assert_eq!(
+            db.storage(&address, &BigUint::from(4u8)),
+            BigUint::from(4u8)
+        );
+    }
+}
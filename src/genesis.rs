@@ -0,0 +1,353 @@
+/// geth格式的创世文件
+/// 从alloc中读取每个地址的余额、nonce、代码和存储构建AccountDb，
+/// 并从创世区块头字段构建对应的区块环境。
+/// 数值字段既可以是0x开头的十六进制字符串，也可以是十进制字符串或JSON数字。
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use num_bigint::BigUint;
+use num_traits::{Num, Zero};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::curr_block::BlockEnv;
+use crate::fake_db::{Account, AccountDb};
+use crate::primitives::{Address, B256};
+
+#[derive(Debug)]
+pub enum GenesisError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    // 数值字段格式错误
+    InvalidQuantity(String),
+    // 地址或哈希格式错误
+    InvalidBytes(String),
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenesisError::Io(err) => write!(f, "io error: {}", err),
+            GenesisError::Json(err) => write!(f, "json error: {}", err),
+            GenesisError::InvalidQuantity(value) => write!(f, "invalid quantity: {}", value),
+            GenesisError::InvalidBytes(value) => write!(f, "invalid hex bytes: {}", value),
+        }
+    }
+}
+
+impl std::error::Error for GenesisError {}
+
+impl From<std::io::Error> for GenesisError {
+    fn from(err: std::io::Error) -> Self {
+        GenesisError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for GenesisError {
+    fn from(err: serde_json::Error) -> Self {
+        GenesisError::Json(err)
+    }
+}
+
+/// 创世文件的原始JSON结构
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenesisJson {
+    #[serde(default)]
+    config: HashMap<String, Value>,
+    coinbase: Option<Value>,
+    timestamp: Option<Value>,
+    number: Option<Value>,
+    gas_limit: Option<Value>,
+    base_fee_per_gas: Option<Value>,
+    excess_blob_gas: Option<Value>,
+    difficulty: Option<Value>,
+    mix_hash: Option<String>,
+    parent_hash: Option<String>,
+    #[serde(default)]
+    alloc: HashMap<String, GenesisAccountJson>,
+}
+
+/// alloc中单个账户的JSON结构，状态导出文件中的账户使用相同的格式
+#[derive(Debug, Deserialize)]
+pub(crate) struct GenesisAccountJson {
+    balance: Option<Value>,
+    nonce: Option<Value>,
+    code: Option<String>,
+    #[serde(default)]
+    storage: HashMap<String, Value>,
+}
+
+/// 解析数值，支持0x十六进制、十进制字符串和JSON数字
+pub(crate) fn parse_quantity(value: &Value) -> Result<BigUint, GenesisError> {
+    let invalid = || GenesisError::InvalidQuantity(value.to_string());
+    match value {
+        Value::Number(number) => number.as_u64().map(BigUint::from).ok_or_else(invalid),
+        Value::String(s) => {
+            let parsed = match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+                Some("") => Ok(BigUint::from(0u8)),
+                Some(hex) => BigUint::from_str_radix(hex, 16),
+                None => BigUint::from_str_radix(s, 10),
+            };
+            parsed.map_err(|_| invalid())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub(crate) fn parse_optional_quantity(value: &Option<Value>) -> Result<BigUint, GenesisError> {
+    value
+        .as_ref()
+        .map(parse_quantity)
+        .unwrap_or(Ok(BigUint::from(0u8)))
+}
+
+pub(crate) fn parse_address(s: &str) -> Result<Address, GenesisError> {
+    s.parse()
+        .map_err(|_| GenesisError::InvalidBytes(s.to_string()))
+}
+
+pub(crate) fn parse_b256(s: &str) -> Result<B256, GenesisError> {
+    s.parse()
+        .map_err(|_| GenesisError::InvalidBytes(s.to_string()))
+}
+
+pub(crate) fn parse_bytes(s: &str) -> Result<Vec<u8>, GenesisError> {
+    hex::decode(s.strip_prefix("0x").unwrap_or(s))
+        .map_err(|_| GenesisError::InvalidBytes(s.to_string()))
+}
+
+/// 解析alloc，值为0的存储槽不写入
+pub(crate) fn parse_alloc(
+    alloc: &HashMap<String, GenesisAccountJson>,
+) -> Result<HashMap<Address, Account>, GenesisError> {
+    let mut accounts = HashMap::new();
+    for (address, account) in alloc {
+        let mut storage = HashMap::new();
+        for (slot, value) in &account.storage {
+            let value = parse_quantity(value)?;
+            if value != BigUint::from(0u8) {
+                storage.insert(parse_quantity(&Value::String(slot.clone()))?, value);
+            }
+        }
+        let code = match &account.code {
+            Some(code) => parse_bytes(code)?,
+            None => Vec::new(),
+        };
+        accounts.insert(
+            parse_address(address)?,
+            Account::new(
+                parse_optional_quantity(&account.balance)?,
+                parse_optional_quantity(&account.nonce)?,
+                storage,
+                code,
+            ),
+        );
+    }
+    Ok(accounts)
+}
+
+/// 创世状态
+#[derive(Debug, Clone)]
+pub struct Genesis {
+    pub chain_id: BigUint,
+    pub coinbase: Address,
+    pub timestamp: BigUint,
+    pub number: BigUint,
+    pub gas_limit: BigUint,
+    pub base_fee: BigUint,
+    pub excess_blob_gas: BigUint,
+    pub difficulty: BigUint,
+    pub mix_hash: B256,
+    pub parent_hash: B256,
+    pub alloc: HashMap<Address, Account>,
+}
+
+impl Genesis {
+    /// 解析创世JSON
+    /// ```
+    /// use mini_evm::genesis::Genesis;
+    /// let genesis = Genesis::from_json(r#"{
+    ///     "config": {"chainId": 1},
+    ///     "alloc": {
+    ///         "0x1000000000000000000000000000000000000c42": {
+    ///             "balance": "0x64",
+    ///             "code": "0x60426000526001601ff3",
+    ///             "storage": {"0x01": "0x02"}
+    ///         }
+    ///     }
+    /// }"#).unwrap();
+    /// let db = genesis.to_db();
+    /// assert_eq!(genesis.alloc.len(), 1);
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, GenesisError> {
+        let raw: GenesisJson = serde_json::from_str(json)?;
+        let alloc = parse_alloc(&raw.alloc)?;
+        let coinbase = match &raw.coinbase {
+            Some(coinbase) => Address::from_word(&parse_quantity(coinbase)?),
+            None => Address::ZERO,
+        };
+        let hash = |value: &Option<String>| match value {
+            Some(value) => parse_b256(value),
+            None => Ok(B256::ZERO),
+        };
+        Ok(Self {
+            chain_id: parse_optional_quantity(&raw.config.get("chainId").cloned())?,
+            coinbase,
+            timestamp: parse_optional_quantity(&raw.timestamp)?,
+            number: parse_optional_quantity(&raw.number)?,
+            gas_limit: parse_optional_quantity(&raw.gas_limit)?,
+            base_fee: parse_optional_quantity(&raw.base_fee_per_gas)?,
+            excess_blob_gas: parse_optional_quantity(&raw.excess_blob_gas)?,
+            difficulty: parse_optional_quantity(&raw.difficulty)?,
+            mix_hash: hash(&raw.mix_hash)?,
+            parent_hash: hash(&raw.parent_hash)?,
+            alloc,
+        })
+    }
+
+    /// 读取创世文件
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, GenesisError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// 以alloc作为初始状态的账户数据库
+    pub fn to_db(&self) -> AccountDb {
+        let mut db = AccountDb::new();
+        for (address, account) in &self.alloc {
+            db.insert(*address, account.clone());
+        }
+        db
+    }
+
+    /// 创世文件对应的区块环境
+    /// 合并后prevrandao取自mixHash，合并前取difficulty
+    pub fn block(&self) -> BlockEnv {
+        let mut block = BlockEnv::default()
+            .with_chain_id(self.chain_id.clone())
+            .with_coinbase(self.coinbase)
+            .with_timestamp(self.timestamp.clone())
+            .with_number(self.number.clone())
+            .with_gas_limit(self.gas_limit.clone())
+            .with_base_fee(self.base_fee.clone())
+            .with_difficulty(self.difficulty.clone())
+            .with_excess_blob_gas(self.excess_blob_gas.clone());
+        if !self.mix_hash.is_zero() {
+            block = block.with_prevrandao(self.mix_hash);
+        }
+        if !self.number.is_zero() && !self.parent_hash.is_zero() {
+            block.insert_block_hash(&self.number - 1u8, self.parent_hash);
+        }
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+
+    #[test]
+    fn test_mock_genesis() {
+        let genesis = Genesis::from_file("fixtures/mock_genesis.json").unwrap();
+        let mut db = genesis.to_db();
+        let address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse()
+            .unwrap();
+        let info = db.basic(&address).unwrap();
+        assert_eq!(info.balance, BigUint::from(100u8));
+        assert_eq!(info.nonce, BigUint::from(1u8));
+        assert_eq!(
+            db.code_by_hash(&info.code_hash),
+            vec![0x60, 0x00, 0x60, 0x00]
+        );
+
+        let block = genesis.block();
+        assert_eq!(block.get_number(), &BigUint::from(17871709u32));
+        assert_eq!(block.get_timestamp(), &BigUint::from(1625900000u32));
+        assert_eq!(
+            block.get_coinbase().to_word(),
+            BigUint::from(0x388c818ca8b9251b393131c08a736a67u128)
+        );
+        assert_eq!(block.get_chainid(), &BigUint::from(1u8));
+        // 与默认区块环境的gas上限一致
+        assert_eq!(block.get_gaslimit(), &BigUint::from(30_000_000u32));
+        assert_eq!(block.get_gaslimit(), BlockEnv::init().get_gaslimit());
+    }
+
+    #[test]
+    fn test_genesis_storage() {
+        Lazy::force(&INIT_LOG);
+        let genesis = Genesis::from_json(
+            r#"{
+                "config": {"chainId": "0x539"},
+                "number": 7,
+                "alloc": {
+                    "9bbfed6889322e016e0a02ee459d306fc19545d8": {"balance": "1000000000000000000000"},
+                    "0x100000000000000000000000000000000000c0de": {
+                        "code": "0x600054600101600055",
+                        "storage": {"0x0": "0x29", "0x1": "0x0"}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(genesis.chain_id, BigUint::from(1337u32));
+        let counter: Address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let db = crate::db::shared(genesis.to_db());
+        // CALL计数器合约
+        let bytes =
+            hex::decode("6000600060006000600173100000000000000000000000000000000000c0de5af1")
+                .unwrap();
+        let txn = Transaction::init(
+            num_traits::zero(),
+            BigUint::from(1u8),
+            BigUint::from(100000u32),
+            counter,
+            num_traits::zero(),
+            "".to_string(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8"
+                .parse()
+                .unwrap(),
+            num_traits::zero(),
+            num_traits::zero(),
+            num_traits::zero(),
+        );
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn, db.clone());
+        evm_test.current_block = genesis.block();
+        evm_test.run();
+        assert!(evm_test.success);
+        assert_eq!(
+            db.borrow_mut().storage(&counter, &BigUint::from(0u8)),
+            BigUint::from(0x2au8)
+        );
+    }
+
+    #[test]
+    fn test_invalid_genesis() {
+        assert!(matches!(
+            Genesis::from_json(r#"{"alloc": {"0x12": {}}}"#),
+            Err(GenesisError::InvalidBytes(_))
+        ));
+        assert!(matches!(
+            Genesis::from_json(r#"{"number": "0xzz"}"#),
+            Err(GenesisError::InvalidQuantity(_))
+        ));
+        assert!(matches!(
+            Genesis::from_json("{"),
+            Err(GenesisError::Json(_))
+        ));
+    }
+}
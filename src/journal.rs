@@ -0,0 +1,561 @@
+/// 带日志的世界状态
+/// 所有状态修改（余额、nonce、代码、存储、账户创建与销毁、日志、瞬时存储）都会记录到journal中，
+/// 在调用和合约创建的边界设置检查点，子调用失败时回滚到检查点，交易结束时统一写回数据库。
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use log::*;
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+use crate::db::{AccountInfo, SharedDb};
+use crate::log_entry::LogEntry;
+use crate::primitives::{Address, B256};
+use crate::utils::keccak256;
+
+/// 存储槽，记录交易开始时的值和当前值
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StorageSlot {
+    pub original_value: BigUint,
+    pub present_value: BigUint,
+}
+
+/// 已加载到内存中的账户
+#[derive(Debug, Clone, Default)]
+pub struct JournalAccount {
+    pub info: AccountInfo,
+    // 已读取或修改过的存储槽
+    pub storage: HashMap<BigUint, StorageSlot>,
+    // 数据库中是否存在该账户
+    pub exists: bool,
+    // 本次交易中是否修改过
+    pub is_touched: bool,
+    // 本次交易中创建的合约，存储不需要从数据库读取
+    pub is_created: bool,
+    // 本次交易中是否已自毁
+    pub is_destroyed: bool,
+}
+
+/// 单条状态修改记录，回滚时按相反顺序撤销
+#[derive(Debug, Clone)]
+pub enum JournalEntry {
+    AccountTouched {
+        address: Address,
+    },
+    // 合约创建和自毁会整体替换账户，记录修改前的完整账户
+    AccountCreated {
+        address: Address,
+        previous: Box<JournalAccount>,
+    },
+    AccountDestroyed {
+        address: Address,
+        previous: Box<JournalAccount>,
+    },
+    BalanceChanged {
+        address: Address,
+        previous: BigUint,
+    },
+    NonceChanged {
+        address: Address,
+        previous: BigUint,
+    },
+    CodeChanged {
+        address: Address,
+        previous_hash: B256,
+        previous_code: Vec<u8>,
+    },
+    StorageChanged {
+        address: Address,
+        slot: BigUint,
+        previous: BigUint,
+    },
+    LogAdded,
+    TransientStorageChanged {
+        address: Address,
+        slot: BigUint,
+        previous: BigUint,
+    },
+}
+
+/// 检查点，即设置时journal的长度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JournalCheckpoint(usize);
+
+#[derive(Debug)]
+pub struct JournaledState {
+    pub db: SharedDb,
+    accounts: HashMap<Address, JournalAccount>,
+    journal: Vec<JournalEntry>,
+    logs: Vec<LogEntry>,
+    transient_storage: HashMap<(Address, BigUint), BigUint>,
+}
+
+/// 虚拟机与子调用共享的世界状态
+pub type SharedState = Rc<RefCell<JournaledState>>;
+
+impl JournaledState {
+    pub fn new(db: SharedDb) -> Self {
+        Self {
+            db,
+            accounts: HashMap::new(),
+            journal: Vec::new(),
+            logs: Vec::new(),
+            transient_storage: HashMap::new(),
+        }
+    }
+
+    /// 包装为可在父子调用间共享的句柄
+    pub fn shared(db: SharedDb) -> SharedState {
+        Rc::new(RefCell::new(Self::new(db)))
+    }
+
+    /// 加载账户，首次访问时从数据库读取
+    pub fn load_account(&mut self, address: &Address) -> &mut JournalAccount {
+        if !self.accounts.contains_key(address) {
+            let mut db = self.db.borrow_mut();
+            let account = match db.basic(address) {
+                Some(mut info) => {
+                    if info.code.is_none() {
+                        info.code = Some(db.code_by_hash(&info.code_hash));
+                    }
+                    JournalAccount {
+                        info,
+                        exists: true,
+                        ..Default::default()
+                    }
+                }
+                None => JournalAccount::default(),
+            };
+            drop(db);
+            self.accounts.insert(*address, account);
+        }
+        self.accounts.get_mut(address).unwrap()
+    }
+
+    /// 账户是否存在（数据库中已有或本次交易中被修改过）
+    pub fn exists(&mut self, address: &Address) -> bool {
+        let account = self.load_account(address);
+        (account.exists || account.is_touched) && !account.is_destroyed
+    }
+
+    pub fn balance(&mut self, address: &Address) -> BigUint {
+        self.load_account(address).info.balance.clone()
+    }
+
+    pub fn nonce(&mut self, address: &Address) -> BigUint {
+        self.load_account(address).info.nonce.clone()
+    }
+
+    pub fn code(&mut self, address: &Address) -> Vec<u8> {
+        self.load_account(address)
+            .info
+            .code
+            .clone()
+            .unwrap_or_default()
+    }
+
+    pub fn code_hash(&mut self, address: &Address) -> B256 {
+        self.load_account(address).info.code_hash
+    }
+
+    /// 标记账户被修改，交易结束时需要写回
+    fn touch(&mut self, address: &Address) {
+        let account = self.load_account(address);
+        if !account.is_touched {
+            account.is_touched = true;
+            self.journal
+                .push(JournalEntry::AccountTouched { address: *address });
+        }
+    }
+
+    pub fn set_balance(&mut self, address: &Address, balance: BigUint) {
+        self.touch(address);
+        let account = self.load_account(address);
+        let previous = std::mem::replace(&mut account.info.balance, balance);
+        self.journal.push(JournalEntry::BalanceChanged {
+            address: *address,
+            previous,
+        });
+    }
+
+    /// 转账，余额不足时返回false且不修改状态
+    pub fn transfer(&mut self, from: &Address, to: &Address, value: &BigUint) -> bool {
+        let from_balance = self.balance(from);
+        if &from_balance < value {
+            return false;
+        }
+        self.set_balance(from, from_balance - value);
+        let to_balance = self.balance(to);
+        self.set_balance(to, to_balance + value);
+        true
+    }
+
+    /// nonce加1，返回修改前的nonce
+    pub fn increment_nonce(&mut self, address: &Address) -> BigUint {
+        self.touch(address);
+        let account = self.load_account(address);
+        let previous = account.info.nonce.clone();
+        account.info.nonce += 1u8;
+        self.journal.push(JournalEntry::NonceChanged {
+            address: *address,
+            previous: previous.clone(),
+        });
+        previous
+    }
+
+    pub fn set_code(&mut self, address: &Address, code: Vec<u8>) {
+        self.touch(address);
+        let account = self.load_account(address);
+        let previous_hash =
+            std::mem::replace(&mut account.info.code_hash, B256::from(keccak256(&code)));
+        let previous_code = account.info.code.replace(code).unwrap_or_default();
+        self.journal.push(JournalEntry::CodeChanged {
+            address: *address,
+            previous_hash,
+            previous_code,
+        });
+    }
+
+    /// 在地址上创建新合约账户，保留已有余额，清空存储
+    pub fn create_account(&mut self, address: &Address) {
+        self.touch(address);
+        let account = self.load_account(address);
+        let previous = account.clone();
+        let balance = account.info.balance.clone();
+        *account = JournalAccount {
+            info: AccountInfo {
+                balance,
+                ..Default::default()
+            },
+            exists: account.exists,
+            is_touched: true,
+            is_created: true,
+            ..Default::default()
+        };
+        self.journal.push(JournalEntry::AccountCreated {
+            address: *address,
+            previous: Box::new(previous),
+        });
+    }
+
+    /// 自毁账户，余额转给target
+    pub fn selfdestruct(&mut self, address: &Address, target: &Address) {
+        let balance = self.balance(address);
+        if address != target {
+            let target_balance = self.balance(target);
+            self.set_balance(target, target_balance + &balance);
+        }
+        self.touch(address);
+        let account = self.load_account(address);
+        let previous = account.clone();
+        account.info.balance = BigUint::zero();
+        account.is_destroyed = true;
+        self.journal.push(JournalEntry::AccountDestroyed {
+            address: *address,
+            previous: Box::new(previous),
+        });
+    }
+
+    /// 读取存储槽
+    pub fn sload(&mut self, address: &Address, slot: &BigUint) -> BigUint {
+        let account = self.load_account(address);
+        if let Some(value) = account.storage.get(slot) {
+            return value.present_value.clone();
+        }
+        // 新创建的合约存储为空，无需访问数据库
+        let value = if account.is_created {
+            BigUint::zero()
+        } else {
+            self.db.borrow_mut().storage(address, slot)
+        };
+        self.load_account(address).storage.insert(
+            slot.clone(),
+            StorageSlot {
+                original_value: value.clone(),
+                present_value: value.clone(),
+            },
+        );
+        value
+    }
+
+    /// 写入存储槽
+    pub fn sstore(&mut self, address: &Address, slot: BigUint, value: BigUint) {
+        let previous = self.sload(address, &slot);
+        self.touch(address);
+        let account = self.load_account(address);
+        account.storage.get_mut(&slot).unwrap().present_value = value;
+        self.journal.push(JournalEntry::StorageChanged {
+            address: *address,
+            slot,
+            previous,
+        });
+    }
+
+    /// 读取瞬时存储（EIP-1153），交易结束时清空
+    pub fn tload(&mut self, address: &Address, slot: &BigUint) -> BigUint {
+        self.transient_storage
+            .get(&(*address, slot.clone()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn tstore(&mut self, address: &Address, slot: BigUint, value: BigUint) {
+        let key = (*address, slot.clone());
+        let previous = if value.is_zero() {
+            self.transient_storage.remove(&key)
+        } else {
+            self.transient_storage.insert(key, value)
+        };
+        self.journal.push(JournalEntry::TransientStorageChanged {
+            address: *address,
+            slot,
+            previous: previous.unwrap_or_default(),
+        });
+    }
+
+    /// 历史区块哈希，由数据库提供
+    pub fn block_hash(&mut self, number: &BigUint) -> B256 {
+        self.db.borrow_mut().block_hash(number)
+    }
+
+    pub fn log(&mut self, log: LogEntry) {
+        self.logs.push(log);
+        self.journal.push(JournalEntry::LogAdded);
+    }
+
+    /// 设置检查点
+    pub fn checkpoint(&mut self) -> JournalCheckpoint {
+        JournalCheckpoint(self.journal.len())
+    }
+
+    /// 保留检查点之后的修改，修改仍可被外层检查点回滚
+    pub fn checkpoint_commit(&mut self, _checkpoint: JournalCheckpoint) {}
+
+    /// 回滚检查点之后的所有修改
+    pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
+        info!("回滚{}条状态修改", self.journal.len() - checkpoint.0);
+        while self.journal.len() > checkpoint.0 {
+            let entry = self.journal.pop().unwrap();
+            self.revert_entry(entry);
+        }
+    }
+
+    fn revert_entry(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::AccountTouched { address } => {
+                self.load_account(&address).is_touched = false;
+            }
+            JournalEntry::AccountCreated { address, previous }
+            | JournalEntry::AccountDestroyed { address, previous } => {
+                self.accounts.insert(address, *previous);
+            }
+            JournalEntry::BalanceChanged { address, previous } => {
+                self.load_account(&address).info.balance = previous;
+            }
+            JournalEntry::NonceChanged { address, previous } => {
+                self.load_account(&address).info.nonce = previous;
+            }
+            JournalEntry::CodeChanged {
+                address,
+                previous_hash,
+                previous_code,
+            } => {
+                let account = self.load_account(&address);
+                account.info.code_hash = previous_hash;
+                account.info.code = Some(previous_code);
+            }
+            JournalEntry::StorageChanged {
+                address,
+                slot,
+                previous,
+            } => {
+                if let Some(value) = self.load_account(&address).storage.get_mut(&slot) {
+                    value.present_value = previous;
+                }
+            }
+            JournalEntry::LogAdded => {
+                self.logs.pop();
+            }
+            JournalEntry::TransientStorageChanged {
+                address,
+                slot,
+                previous,
+            } => {
+                let key = (address, slot);
+                if previous.is_zero() {
+                    self.transient_storage.remove(&key);
+                } else {
+                    self.transient_storage.insert(key, previous);
+                }
+            }
+        }
+    }
+
+    /// 交易结束，将修改写回数据库并清空journal和瞬时存储，返回交易产生的日志
+    pub fn finalize(&mut self) -> Vec<LogEntry> {
+        let mut db = self.db.borrow_mut();
+        for (address, account) in self.accounts.drain() {
+            if !account.is_touched {
+                continue;
+            }
+            if account.is_destroyed {
+                db.remove_account(&address);
+                continue;
+            }
+            // 重新创建的合约需要先清空数据库中的旧存储
+            if account.is_created && account.exists {
+                db.remove_account(&address);
+            }
+            db.insert_account(&address, account.info);
+            for (slot, value) in account.storage {
+                if value.present_value != value.original_value {
+                    db.insert_storage(&address, slot, value.present_value);
+                }
+            }
+        }
+        db.commit();
+        self.journal.clear();
+        self.transient_storage.clear();
+        std::mem::take(&mut self.logs)
+    }
+
+    /// 丢弃本次交易的所有修改
+    pub fn discard(&mut self) {
+        self.checkpoint_revert(JournalCheckpoint(0));
+        self.accounts.clear();
+        self.transient_storage.clear();
+        self.logs.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::shared;
+    use crate::evm::*;
+    use crate::fake_db::{Account, AccountDb};
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+
+    const ALICE: &str = "0x9bbfed6889322e016e0a02ee459d306fc19545d8";
+    const BOB: &str = "0x1000000000000000000000000000000000000c42";
+    const REVERTER: &str = "0x100000000000000000000000000000000000beef";
+
+    fn address(s: &str) -> Address {
+        s.parse().unwrap()
+    }
+
+    fn mock_db() -> SharedDb {
+        let mut db = AccountDb::mock();
+        // PUSH1 0 PUSH1 0 REVERT
+        db.insert(
+            address(REVERTER),
+            Account::new(
+                BigUint::zero(),
+                BigUint::zero(),
+                HashMap::new(),
+                hex::decode("60006000fd").unwrap(),
+            ),
+        );
+        shared(db)
+    }
+
+    #[test]
+    fn test_checkpoint_revert() {
+        let mut state = JournaledState::new(mock_db());
+        let alice = &address(ALICE);
+        let bob = &address(BOB);
+        let outer = state.checkpoint();
+        assert!(state.transfer(alice, bob, &BigUint::from(10u8)));
+        state.sstore(bob, BigUint::from(1u8), BigUint::from(7u8));
+
+        let inner = state.checkpoint();
+        state.increment_nonce(alice);
+        state.sstore(bob, BigUint::from(1u8), BigUint::from(8u8));
+        state.tstore(bob, BigUint::from(1u8), BigUint::from(9u8));
+        state.log(LogEntry::init(*bob, vec![], vec![]));
+        state.checkpoint_revert(inner);
+
+        assert_eq!(state.nonce(alice), BigUint::from(1u8));
+        assert_eq!(state.sload(bob, &BigUint::from(1u8)), BigUint::from(7u8));
+        assert_eq!(state.tload(bob, &BigUint::from(1u8)), BigUint::zero());
+        assert_eq!(state.balance(bob), BigUint::from(10u8));
+
+        state.checkpoint_revert(outer);
+        assert_eq!(state.balance(alice), BigUint::from(100u8));
+        assert_eq!(state.balance(bob), BigUint::zero());
+        assert_eq!(state.sload(bob, &BigUint::from(1u8)), BigUint::zero());
+    }
+
+    #[test]
+    fn test_finalize() {
+        let db = mock_db();
+        let mut state = JournaledState::new(db.clone());
+        let alice = &address(ALICE);
+        let bob = &address(BOB);
+        state.transfer(alice, bob, &BigUint::from(10u8));
+        state.sstore(bob, BigUint::from(1u8), BigUint::from(7u8));
+        state.tstore(bob, BigUint::from(1u8), BigUint::from(9u8));
+        state.log(LogEntry::init(*bob, vec![], vec![]));
+        assert_eq!(state.finalize().len(), 1);
+
+        let mut db = db.borrow_mut();
+        assert_eq!(
+            db.basic(&address(ALICE)).unwrap().balance,
+            BigUint::from(90u8)
+        );
+        assert_eq!(db.basic(bob).unwrap().balance, BigUint::from(10u8));
+        assert_eq!(db.storage(bob, &BigUint::from(1u8)), BigUint::from(7u8));
+        drop(db);
+        assert_eq!(state.tload(bob, &BigUint::from(1u8)), BigUint::zero());
+    }
+
+    #[test]
+    fn test_sub_call_revert() {
+        Lazy::force(&INIT_LOG);
+        // 携带1 wei调用会回滚的合约
+        let bytes =
+            hex::decode("6000600060006000600173100000000000000000000000000000000000beef5af1")
+                .unwrap();
+        let db = mock_db();
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        assert!(evm_test.success);
+        assert_eq!(
+            hex::encode(evm_test.stack.get(1).data),
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        let mut db = db.borrow_mut();
+        assert_eq!(
+            db.basic(&address(ALICE)).unwrap().balance,
+            BigUint::from(100u8)
+        );
+        assert_eq!(
+            db.basic(&address(REVERTER)).unwrap().balance,
+            BigUint::zero()
+        );
+    }
+
+    #[test]
+    fn test_top_level_revert() {
+        Lazy::force(&INIT_LOG);
+        // 向0x1000..0c42转账1 wei并记录日志后REVERT
+        let bytes = hex::decode(
+            "6000600060006000600173100000000000000000000000000000000000\
+             0c425af15060006000a060006000fd",
+        )
+        .unwrap();
+        let db = mock_db();
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        assert!(!evm_test.success);
+        assert!(evm_test.logs.is_empty());
+        let mut db = db.borrow_mut();
+        assert_eq!(
+            db.basic(&address(ALICE)).unwrap().balance,
+            BigUint::from(100u8)
+        );
+        assert_eq!(db.basic(&address(BOB)).unwrap().balance, BigUint::zero());
+    }
+}
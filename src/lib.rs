@@ -1,10 +1,22 @@
 pub mod const_var;
 pub mod curr_block;
+pub mod db;
+pub mod estimate_gas;
 pub mod evm;
 pub mod fake_db;
+pub mod file_db;
+pub mod genesis;
+pub mod journal;
 pub mod log_entry;
 pub mod log_utils;
 pub mod ops;
+pub mod precompile;
+pub mod primitives;
+pub mod proof;
+pub mod rlp;
 pub mod stack;
+pub mod state_dump;
 pub mod transaction;
+pub mod tx_envelope;
+pub mod trie;
 pub mod utils;
@@ -1,17 +1,29 @@
 use num_bigint::BigUint;
+
+use crate::primitives::Address;
 #[derive(Debug, Clone)]
 pub struct LogEntry {
-    address: String,
+    address: Address,
     data: Vec<u8>,
     topics: Vec<BigUint>,
 }
 
 impl LogEntry {
-    pub fn init(address: String, data: Vec<u8>, topics: Vec<BigUint>) -> Self {
+    pub fn init(address: Address, data: Vec<u8>, topics: Vec<BigUint>) -> Self {
         Self {
-            address: address,
-            data: data,
-            topics: topics,
+            address,
+            data,
+            topics,
         }
     }
+    /// 产生日志的合约地址
+    pub fn get_address(&self) -> &Address {
+        &self.address
+    }
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn get_topics(&self) -> &[BigUint] {
+        &self.topics
+    }
 }
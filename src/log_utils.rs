@@ -1,5 +1,3 @@
-use std::collections::{hash_map, HashMap};
-
 use crate::{ops::memory, utils::*};
 use log::*;
 use num_bigint::BigUint;
@@ -69,10 +67,6 @@ impl LogTemplate {
         );
     }
     
-    pub fn log_storage_store_val(&self,hash_map:HashMap<BigUint, (BigUint, u8)>) {
-        info!("{}的存储值:{:?}", self.op_name, hash_map);
-    }
-    
     pub fn log_memory_store_val(&self, memory: Vec<u8>) {
         info!("{}的存储值:{:?}", self.op_name, vec_to_hex_string(memory));
     }
@@ -1,4 +1,5 @@
 use crate::ops::traits::AccountTraits;
+use crate::primitives::Address;
 use crate::utils::*;
 use crate::{evm::Evm, stack::StackData};
 use num_bigint::BigUint;
@@ -10,8 +11,8 @@ impl AccountTraits for Evm {
             panic!("Stack underflow");
         }
         let addr_int = get_uint256(self.stack.pop());
-        let addr_str = format!("0x{}", vec_to_hex_string(addr_int.to_bytes_be()));
-        let balance = get_account_db().get_account(addr_str).balance.clone();
+        let address = Address::from_word(&addr_int);
+        let balance = self.state.borrow_mut().balance(&address);
         self.stack.push(StackData::new(balance.to_bytes_be(), 0u8));
     }
     fn extcodecopy(&mut self) {
@@ -22,12 +23,15 @@ impl AccountTraits for Evm {
         let mem_offset = get_uint256(self.stack.pop());
         let code_offset = get_uint256(self.stack.pop());
         let lenght = get_uint256(self.stack.pop());
-        let addr_str = format!("0x{}", vec_to_hex_string(addr_int.to_bytes_be()));
+        let address = Address::from_word(&addr_int);
         let offset = (lenght.clone() + mem_offset.clone()).to_usize().unwrap();
         let code_offset_len = (lenght.clone() + code_offset.clone()).to_usize().unwrap();
-        let code = get_account_db().get_account(addr_str).code.clone()
-            [code_offset.to_usize().unwrap()..code_offset_len]
-            .to_vec();
+        let mut code = self.account_code(&address);
+        // 超出代码长度的部分以0填充
+        if code.len() < code_offset_len {
+            code.resize(code_offset_len, 0u8);
+        }
+        let code = code[code_offset.to_usize().unwrap()..code_offset_len].to_vec();
 
         // if code.len() < 32 {
         //     // 如果字节长度不足 32 字节，前面填充 0
@@ -53,9 +57,15 @@ impl AccountTraits for Evm {
             panic!("Stack underflow");
         }
         let addr_int = get_uint256(self.stack.pop());
-        let addr_str = format!("0x{}", vec_to_hex_string(addr_int.to_bytes_be()));
-        let code = get_account_db().get_account(addr_str).code.clone();
-        let code_hash: Vec<u8> = keccak256(&code).to_vec();
+        let address = Address::from_word(&addr_int);
+        // 不存在的账户哈希为0
+        let mut state = self.state.borrow_mut();
+        let code_hash: Vec<u8> = if state.exists(&address) {
+            state.code_hash(&address).as_bytes().to_vec()
+        } else {
+            vec![0u8]
+        };
+        drop(state);
         self.stack.push(StackData::new(code_hash, 0u8));
     }
     fn extcodesize(&mut self) {
@@ -63,8 +73,8 @@ impl AccountTraits for Evm {
             panic!("Stack underflow");
         }
         let addr_int = get_uint256(self.stack.pop());
-        let addr_str = format!("0x{}", vec_to_hex_string(addr_int.to_bytes_be()));
-        let code = get_account_db().get_account(addr_str).code.clone();
+        let address = Address::from_word(&addr_int);
+        let code = self.account_code(&address);
         self.stack
             .push(StackData::new(code.len().to_be_bytes().to_vec(), 0u8));
     }
@@ -1,4 +1,6 @@
+use crate::const_var::{CALL_STIPEND, CALL_VALUE_GAS, NEW_ACCOUNT_GAS};
 use crate::ops::traits::*;
+use crate::primitives::Address;
 use crate::stack::StackData;
 use crate::utils::*;
 use crate::{evm::Evm, transaction::Transaction};
@@ -6,6 +8,19 @@ use log::*;
 use num_bigint::BigUint;
 use num_traits::{zero, ToPrimitive, Zero};
 
+/// 子调用消耗的gas中由调用者承担的部分
+/// 2300的津贴是额外给予子调用的，不从调用者的gas中扣除
+fn caller_gas_cost(sub_gas_used: &BigUint, with_stipend: bool) -> BigUint {
+    let stipend = BigUint::from(CALL_STIPEND);
+    if !with_stipend {
+        sub_gas_used.clone()
+    } else if sub_gas_used > &stipend {
+        sub_gas_used - stipend
+    } else {
+        zero()
+    }
+}
+
 impl Call for Evm {
     /// call指令
     /// ```
@@ -27,11 +42,6 @@ impl Call for Evm {
         let mem_out_offset = get_uint256(self.stack.pop());
         let mem_out_size = get_uint256(self.stack.pop());
 
-        if self.is_static && value.is_zero() {
-            self.success = false;
-            panic!("State changing operation detected during STATICCALL!");
-        }
-
         // 拓展内存
         if self.memory.len() < &mem_in_offset.to_usize().unwrap() + &mem_in_size.to_usize().unwrap()
         {
@@ -45,57 +55,170 @@ impl Call for Evm {
             ..mem_in_offset.to_usize().unwrap() + mem_in_size.to_usize().unwrap()]
             .to_vec();
 
-        //获取调用账户
-        let mut db = get_account_db_2();
-        // let mut db_temp = get_account_db_for_calltest();
-        info!("caller:{}", self.txn.get_caller());
-        info!("to:{}", format!("0x{}", hex::encode(to.to_bytes_be())));
-        let account_source = db.get_account_mut(self.txn.get_caller());
+        //调用者为当前执行的账户
+        let caller = self.txn.get_this_addr();
+        let to_addr = Address::from_word(&to);
+        info!("caller:{}", caller);
+        info!("to:{}", to_addr);
+
+        // 带value的调用额外消耗9000 gas，目标账户不存在时还需25000 gas创建账户（EIP-161）
+        if !value.is_zero() {
+            self.gas_used += BigUint::from(CALL_VALUE_GAS);
+            if !self.state.borrow_mut().exists(&to_addr) {
+                self.gas_used += BigUint::from(NEW_ACCOUNT_GAS);
+            }
+        }
+        if self.is_out_of_gas() {
+            return;
+        }
+
+        // 子调用失败时回滚转账和子调用中的所有状态修改
+        let checkpoint = self.state.borrow_mut().checkpoint();
 
-        //判断调用账户是否有足够的资金
-        if &account_source.balance < &value {
-            self.success = false;
+        //判断调用账户是否有足够的资金并转账
+        if !self.state.borrow_mut().transfer(&caller, &to_addr, &value) {
             info!("insufficient balance");
             self.stack
                 .push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
+            return;
         }
 
-        //更新余额
-        account_source.balance -= value.clone(); //判断调用账户是否有足够的资金
-        if &account_source.balance < &value {
-            self.success = false;
-            info!("insufficient balance");
+        // 转账调用额外获得2300的gas津贴
+        let with_stipend = !value.is_zero();
+        let mut sub_gas = self.sub_call_gas_limit(&gas);
+        if with_stipend {
+            sub_gas += BigUint::from(CALL_STIPEND);
+        }
+
+        //构建上下文
+        let txn = Transaction::init(
+            zero(),
+            self.txn.get_gas_price().clone(),
+            sub_gas,
+            to_addr,
+            value,
+            hex::encode(data),
+            caller,
+            self.txn.get_origin(),
+            to_addr,
+            zero(),
+            zero(),
+            zero(),
+        );
+
+        // 初始化子EVM执行环境
+        let evm_sub = self.run_sub_call(&to_addr, txn, false);
+        self.gas_used += caller_gas_cost(&evm_sub.gas_used, with_stipend);
+        if evm_sub.success {
+            self.state.borrow_mut().checkpoint_commit(checkpoint);
+        } else {
+            self.state.borrow_mut().checkpoint_revert(checkpoint);
+        }
+
+        // 拓展内存
+        let out_len = (&mem_out_offset + &mem_out_size).to_usize().unwrap();
+        if self.memory.len() < out_len {
+            self.memory.resize(out_len, 0u8);
+        }
+
+        // 返回数据长度可能小于mem_out_size，只复制实际返回的部分
+        let copy_len = mem_out_size
+            .to_usize()
+            .unwrap()
+            .min(evm_sub.return_data.len());
+        let out_offset = mem_out_offset.to_usize().unwrap();
+        self.memory[out_offset..out_offset + copy_len]
+            .copy_from_slice(&evm_sub.return_data[..copy_len]);
+
+        if evm_sub.success {
+            self.stack
+                .push(StackData::new(BigUint::from(1u8).to_bytes_be(), 0u8));
+        } else {
             self.stack
                 .push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
         }
+    }
+    /// callcode指令
+    /// 以目标账户的代码在当前账户的上下文中执行：读写当前账户的存储，msg.sender为当前账户，
+    /// msg.value为传入的value。value从当前账户转给自己，余额不变但仍需检查余额是否足够
+    /// ```
+    /// use mini_evm::evm::Evm;
+    /// let excute_codes = "6001601f5f5f6001731000000000000000000000000000000000000c425ff25f51";
+    /// let bytes = hex::decode(excute_codes).unwrap();
+    /// let mut evm_test = Evm::new(bytes);
+    /// evm_test.run();
+    /// ```
+    fn callcode(&mut self) {
+        if self.stack.len() < 7 {
+            panic!("stack underflow");
+        }
+        let gas = get_uint256(self.stack.pop());
+        let to = get_uint256(self.stack.pop());
+        let value = get_uint256(self.stack.pop());
+        let mem_in_offset = get_uint256(self.stack.pop());
+        let mem_in_size = get_uint256(self.stack.pop());
+        let mem_out_offset = get_uint256(self.stack.pop());
+        let mem_out_size = get_uint256(self.stack.pop());
 
-        //更新余额
-        account_source.balance -= value.clone();
+        // 拓展内存
+        let in_offset = mem_in_offset.to_usize().unwrap();
+        let in_len = in_offset + mem_in_size.to_usize().unwrap();
+        if self.memory.len() < in_len {
+            self.memory.resize(in_len, 0u8);
+        }
+        let data = self.memory[in_offset..in_len].to_vec();
 
-        // //获取目标账户
-        let account_dest = db.get_account_mut(format!("0x{}", hex::encode(to.to_bytes_be())));
+        let this_addr = self.txn.get_this_addr();
+        let code_addr = Address::from_word(&to);
+        info!("callcode:{}", code_addr);
 
-        account_dest.balance += value.clone();
+        // 带value的调用额外消耗9000 gas，value转给自己，不会创建新账户
+        if !value.is_zero() {
+            self.gas_used += BigUint::from(CALL_VALUE_GAS);
+        }
+        if self.is_out_of_gas() {
+            return;
+        }
 
-        //构建上下文
+        // 余额不足时调用失败，不执行目标代码
+        if self.state.borrow_mut().balance(&this_addr) < value {
+            info!("insufficient balance");
+            self.stack
+                .push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
+            return;
+        }
+
+        // 与call相同，带value的调用额外获得2300的gas津贴
+        let with_stipend = !value.is_zero();
+        let mut sub_gas = self.sub_call_gas_limit(&gas);
+        if with_stipend {
+            sub_gas += BigUint::from(CALL_STIPEND);
+        }
+
+        //构建上下文，执行地址仍为当前账户
         let txn = Transaction::init(
             zero(),
             self.txn.get_gas_price().clone(),
-            self.txn.get_gas_limit().clone(),
-            hex::encode(to.to_bytes_be()),
+            sub_gas,
+            this_addr,
             value,
             hex::encode(data),
-            self.txn.get_caller(),
+            this_addr,
             self.txn.get_origin(),
-            hex::encode(to.to_bytes_be()),
+            this_addr,
             zero(),
             zero(),
             zero(),
         );
 
-        // 初始化子EVM执行环境
-        let mut evm_sub = Evm::init_evm(account_dest.code.clone(), txn);
-        evm_sub.run();
+        let checkpoint = self.state.borrow_mut().checkpoint();
+        let evm_sub = self.run_sub_call(&code_addr, txn, false);
+        self.gas_used += caller_gas_cost(&evm_sub.gas_used, with_stipend);
+        if evm_sub.success {
+            self.state.borrow_mut().checkpoint_commit(checkpoint);
+        } else {
+            self.state.borrow_mut().checkpoint_revert(checkpoint);
+        }
 
         // 拓展内存
         let out_len = (&mem_out_offset + &mem_out_size).to_usize().unwrap();
@@ -103,8 +226,14 @@ impl Call for Evm {
             self.memory.resize(out_len, 0u8);
         }
 
-        self.memory[mem_out_offset.to_usize().unwrap()..out_len]
-            .copy_from_slice(&evm_sub.return_data[0..]);
+        // 返回数据长度可能小于mem_out_size，只复制实际返回的部分
+        let copy_len = mem_out_size
+            .to_usize()
+            .unwrap()
+            .min(evm_sub.return_data.len());
+        let out_offset = mem_out_offset.to_usize().unwrap();
+        self.memory[out_offset..out_offset + copy_len]
+            .copy_from_slice(&evm_sub.return_data[..copy_len]);
 
         if evm_sub.success {
             self.stack
@@ -146,16 +275,19 @@ impl Call for Evm {
             ..mem_in_offset.to_usize().unwrap() + mem_in_size.to_usize().unwrap()]
             .to_vec();
 
-        //获取调用账户
-        let mut db = get_account_db_2();
-
-        // //获取目标账户
-        let account_dest = db.get_account_mut(format!("0x{}", hex::encode(to.to_bytes_be())));
-
         // 初始化子EVM执行环境
-        let mut evm_sub = Evm::init_evm(account_dest.code.clone(), self.txn.clone());
-        evm_sub.storage = self.storage.clone();
-        evm_sub.run();
+        // 沿用当前的上下文，子调用读写的是当前账户的存储
+        let mut txn = self.txn.clone();
+        txn.set_gas_limit(self.sub_call_gas_limit(&gas));
+        txn.set_data(hex::encode(data));
+        let checkpoint = self.state.borrow_mut().checkpoint();
+        let evm_sub = self.run_sub_call(&Address::from_word(&to), txn, false);
+        self.gas_used += evm_sub.gas_used.clone();
+        if evm_sub.success {
+            self.state.borrow_mut().checkpoint_commit(checkpoint);
+        } else {
+            self.state.borrow_mut().checkpoint_revert(checkpoint);
+        }
 
         // 拓展内存
         let out_len = (&mem_out_offset + &mem_out_size).to_usize().unwrap();
@@ -163,8 +295,14 @@ impl Call for Evm {
             self.memory.resize(out_len, 0u8);
         }
 
-        self.memory[mem_out_offset.to_usize().unwrap()..out_len]
-            .copy_from_slice(&evm_sub.return_data[0..]);
+        // 返回数据长度可能小于mem_out_size，只复制实际返回的部分
+        let copy_len = mem_out_size
+            .to_usize()
+            .unwrap()
+            .min(evm_sub.return_data.len());
+        let out_offset = mem_out_offset.to_usize().unwrap();
+        self.memory[out_offset..out_offset + copy_len]
+            .copy_from_slice(&evm_sub.return_data[..copy_len]);
 
         if evm_sub.success {
             self.stack
@@ -207,38 +345,47 @@ impl Call for Evm {
             ..mem_in_offset.to_usize().unwrap() + mem_in_size.to_usize().unwrap()]
             .to_vec();
 
-        //获取调用账户
-        let mut db = get_account_db_2();
-
         // //获取目标账户
-        let account_dest = db.get_account_mut(format!("0x{}", hex::encode(to.to_bytes_be())));
+        let to_addr = Address::from_word(&to);
 
         //构建上下文
         let txn = Transaction::init(
             zero(),
             self.txn.get_gas_price().clone(),
-            self.txn.get_gas_limit().clone(),
-            hex::encode(to.to_bytes_be()),
+            self.sub_call_gas_limit(&gas),
+            to_addr,
             zero(),
             hex::encode(data),
             self.txn.get_this_addr(),
             self.txn.get_origin(),
-            hex::encode(to.to_bytes_be()),
+            to_addr,
             zero(),
             zero(),
             zero(),
         );
-        // 初始化子EVM执行环境
-        let mut evm_sub = Evm::init_evm(account_dest.code.clone(), txn);
-        evm_sub.run();
+        // 初始化子EVM执行环境，子调用及其嵌套调用都不能修改状态
+        let checkpoint = self.state.borrow_mut().checkpoint();
+        let evm_sub = self.run_sub_call(&to_addr, txn, true);
+        self.gas_used += evm_sub.gas_used.clone();
+        if evm_sub.success {
+            self.state.borrow_mut().checkpoint_commit(checkpoint);
+        } else {
+            self.state.borrow_mut().checkpoint_revert(checkpoint);
+        }
 
         let out_len = (&mem_out_offset + &mem_out_size).to_usize().unwrap();
         if self.memory.len() < out_len {
             self.memory.resize(out_len, 0u8);
         }
 
-        self.memory[mem_out_offset.to_usize().unwrap()..out_len]
-            .copy_from_slice(&evm_sub.return_data[0..]);
+        // 返回数据长度可能小于mem_out_size，只复制实际返回的部分
+        let copy_len = mem_out_size
+            .to_usize()
+            .unwrap()
+            .min(evm_sub.return_data.len());
+        let out_offset = mem_out_offset.to_usize().unwrap();
+        self.memory[out_offset..out_offset + copy_len]
+            .copy_from_slice(&evm_sub.return_data[..copy_len]);
 
         if evm_sub.success {
             self.stack
@@ -251,8 +398,114 @@ impl Call for Evm {
 }
 #[cfg(test)]
 mod tests {
+    use crate::db::*;
     use crate::evm::*;
+    use crate::fake_db::{Account, AccountDb};
+    use crate::primitives::Address;
+    use crate::transaction::Transaction;
+    use num_bigint::BigUint;
+    use num_traits::zero;
     use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    // 记录调用上下文的合约: slot0 = CALLER, slot1 = CALLVALUE
+    fn recorder() -> Address {
+        "0x100000000000000000000000000000000000c0de".parse().unwrap()
+    }
+
+    fn recorder_db() -> Rc<RefCell<AccountDb>> {
+        let mut db = AccountDb::mock();
+        db.insert(
+            recorder(),
+            Account::new(
+                zero(),
+                zero(),
+                HashMap::new(),
+                hex::decode("3360005534600155").unwrap(),
+            ),
+        );
+        Rc::new(RefCell::new(db))
+    }
+
+    /// 以指定操作码和value调用recorder合约
+    fn call_recorder(op: &str, value: u8) -> (Evm, Rc<RefCell<AccountDb>>) {
+        let bytes = hex::decode(format!(
+            "600060006000600060{:02x}73100000000000000000000000000000000000c0de5a{}",
+            value, op
+        ))
+        .unwrap();
+        let db = recorder_db();
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        (evm_test, db)
+    }
+
+    /// 附带1 wei、转发1000 gas调用目标地址，返回执行后的虚拟机
+    fn call_with_value(op: &str, to: &str, db: Rc<RefCell<AccountDb>>) -> Evm {
+        let bytes = hex::decode(format!("6000600060006000600173{}6103e8{}", to, op)).unwrap();
+        let mut txn = Transaction::mock();
+        txn.set_gas_limit(BigUint::from(100000u32));
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn, db);
+        evm_test.run();
+        assert!(evm_test.success);
+        evm_test
+    }
+
+    #[test]
+    fn test_call_stipend_not_charged() {
+        Lazy::force(&INIT_LOG);
+        // 被调用合约无限循环，耗尽转发的gas和津贴
+        let mut db = AccountDb::mock();
+        db.insert(
+            recorder(),
+            Account::new(zero(), zero(), HashMap::new(), vec![0x5b, 0x60, 0x00, 0x56]),
+        );
+        let db = Rc::new(RefCell::new(db));
+        for op in ["f1", "f2"] {
+            let evm_test =
+                call_with_value(op, "100000000000000000000000000000000000c0de", db.clone());
+            assert_eq!(
+                BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+                BigUint::from(0u8)
+            );
+            // 7个PUSH + 基础费用700 + value费用9000 + 转发的1000 gas，津贴不由调用者承担
+            assert_eq!(
+                evm_test.gas_used,
+                BigUint::from(7u32 * 3 + 700 + 9000 + 1000)
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_value_gas() {
+        Lazy::force(&INIT_LOG);
+        let db = Rc::new(RefCell::new(AccountDb::mock()));
+        let empty = "2000000000000000000000000000000000000000";
+        // 向不存在的账户转账需要额外的25000 gas，子调用没有代码，不消耗gas
+        let evm_test = call_with_value("f1", empty, db.clone());
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(1u8)
+        );
+        assert_eq!(
+            evm_test.gas_used,
+            BigUint::from(7u32 * 3 + 700 + 9000 + 25000)
+        );
+        drop(evm_test);
+        // 账户已经存在后只收取value费用
+        let evm_test = call_with_value("f1", empty, db.clone());
+        assert_eq!(evm_test.gas_used, BigUint::from(7u32 * 3 + 700 + 9000));
+        drop(evm_test);
+        // CALLCODE的value转给自己，不会创建账户
+        let evm_test = call_with_value("f2", "3000000000000000000000000000000000000000", db);
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(1u8)
+        );
+        assert_eq!(evm_test.gas_used, BigUint::from(7u32 * 3 + 700 + 9000));
+    }
 
     #[test]
     fn test_call() {
@@ -283,5 +536,54 @@ mod tests {
         evm_test.run();
         println!("{:?}", evm_test.stack);
     }
-}
 
+    #[test]
+    fn test_call_context() {
+        Lazy::force(&INIT_LOG);
+        let (evm_test, db) = call_recorder("f1", 5);
+        assert!(evm_test.success);
+        drop(evm_test);
+        let this_addr = Transaction::mock().get_this_addr();
+        let mut db = db.borrow_mut();
+        // msg.sender为发起调用的合约，value从该合约转出
+        assert_eq!(db.storage(&recorder(), &zero()), this_addr.to_word());
+        assert_eq!(db.storage(&recorder(), &BigUint::from(1u8)), BigUint::from(5u8));
+        assert_eq!(db.basic(&recorder()).unwrap().balance, BigUint::from(5u8));
+        assert_eq!(db.basic(&this_addr).unwrap().balance, BigUint::from(95u8));
+    }
+
+    #[test]
+    fn test_callcode() {
+        Lazy::force(&INIT_LOG);
+        let (evm_test, db) = call_recorder("f2", 5);
+        assert!(evm_test.success);
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(1u8)
+        );
+        drop(evm_test);
+        let this_addr = Transaction::mock().get_this_addr();
+        let mut db = db.borrow_mut();
+        // 写入的是调用者的存储，msg.sender为调用者自身
+        assert_eq!(db.storage(&this_addr, &zero()), this_addr.to_word());
+        assert_eq!(db.storage(&this_addr, &BigUint::from(1u8)), BigUint::from(5u8));
+        assert_eq!(db.storage(&recorder(), &zero()), zero());
+        // value转给自己，余额不变
+        assert_eq!(db.basic(&this_addr).unwrap().balance, BigUint::from(100u8));
+        assert_eq!(db.basic(&recorder()).unwrap().balance, zero());
+    }
+
+    #[test]
+    fn test_callcode_insufficient_balance() {
+        Lazy::force(&INIT_LOG);
+        let (evm_test, db) = call_recorder("f2", 0xff);
+        assert!(evm_test.success);
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            zero::<BigUint>()
+        );
+        drop(evm_test);
+        let this_addr = Transaction::mock().get_this_addr();
+        assert_eq!(db.borrow_mut().storage(&this_addr, &zero()), zero());
+    }
+}
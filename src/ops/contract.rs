@@ -1,102 +1,117 @@
-use std::collections::HashMap;
-
-use crate::fake_db::{Account, AccountDb};
 use crate::ops::traits::*;
+use crate::primitives::{Address, B256};
+use crate::rlp;
 use crate::stack::StackData;
-use crate::{evm, utils::*};
+use crate::utils::*;
 use crate::{evm::Evm, transaction::Transaction};
 use log::*;
-use num_bigint::{BigUint, ToBigUint};
-use num_traits::{zero, ToPrimitive, Zero};
+use num_bigint::BigUint;
+use num_traits::{zero, ToPrimitive};
 
-use super::account;
-impl Contract for Evm {
-    fn create(&mut self) {
-        if self.stack.len() < 3 {
-            panic!("Stack underflow");
-        }
-        //获取堆栈数据
-        let value = get_uint256(self.stack.pop());
-        let mem_offset = get_uint256(self.stack.pop());
-        let lenght = get_uint256(self.stack.pop());
-
-        //拓展内存
-        // 获取内存需要的长度
-        let len = (&mem_offset + &lenght).to_usize().unwrap();
-        if self.memory.len() < len {
-            self.memory.resize(len, 0u8);
-        }
-
-        //获取初始代码
-        let init_code = self.memory[mem_offset.to_usize().unwrap()..len].to_vec();
-
-        // 检查创建者的余额是否足够
-        let mut binding = get_account_db();
-        info!("创建者地址{}", self.txn.get_this_addr());
-        let creator_account = binding.get_account_mut(self.txn.get_this_addr());
-        if creator_account.balance < value {
-            panic!("Insufficient balance to create contract!");
-        }
+/// CREATE创建的合约地址 keccak256(rlp([创建者地址, nonce]))的后20字节
+/// ```
+/// use mini_evm::ops::contract::create_address;
+/// use num_bigint::BigUint;
+/// let creator = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".parse().unwrap();
+/// let address = create_address(&creator, &BigUint::from(0u8));
+/// assert_eq!(address, "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d".parse().unwrap());
+/// ```
+pub fn create_address(creator: &Address, nonce: &BigUint) -> Address {
+    let hash = keccak256(&rlp::encode(&(creator, nonce)));
+    Address::from_slice(&hash[12..])
+}
 
-        // 扣除创建者指定的金额
-        creator_account.balance -= value.clone();
+/// CREATE2创建的合约地址 keccak256(0xff ++ 创建者地址 ++ salt ++ keccak256(初始代码))的后20字节
+pub fn create2_address(creator: &Address, salt: &BigUint, init_code: &[u8]) -> Address {
+    let mut data_to_hash = vec![0xffu8];
+    data_to_hash.extend_from_slice(creator.as_bytes());
+    data_to_hash.extend_from_slice(B256::from_word(salt).as_bytes());
+    data_to_hash.extend_from_slice(&keccak256(init_code));
+    let hash = keccak256(&data_to_hash);
+    Address::from_slice(&hash[12..])
+}
 
-        // 生成新的合约地址
-        let mut creator_nonce = creator_account.nonce.to_bytes_be();
-        let mut this_address = hex::decode(self.txn.get_this_addr().split_off(2)).unwrap();
-        this_address.append(&mut creator_nonce);
-        let new_contract_address_bytes = keccak256(&this_address);
-        let new_contract_address = format!(
-            "0x{}",
-            hex::encode(&new_contract_address_bytes[(new_contract_address_bytes.len() - 20)..])
-        );
+impl Evm {
+    /// 执行初始代码并部署合约
+    /// 成功时将新合约地址入栈，失败时入栈0
+    fn create_contract(&mut self, value: BigUint, init_code: Vec<u8>, new_contract_address: Address) {
+        let creator = self.txn.get_this_addr();
         info!("新合约地址{}", new_contract_address);
+
         // 构建上下文
         let txn = Transaction::init(
             zero(),
-            self.txn.get_gas_limit().clone(),
-            self.txn.get_gas_limit().clone(),
-            new_contract_address.clone(),
+            self.txn.get_gas_price().clone(),
+            self.sub_call_gas_limit(&self.remaining_gas()),
+            new_contract_address,
             value.clone(),
             hex::encode(init_code.clone()),
-            self.txn.get_this_addr(),
+            creator,
             self.txn.get_origin(),
-            new_contract_address.clone(),
+            new_contract_address,
             zero(),
             zero(),
             zero(),
         );
 
+        // 合约创建失败时回滚新账户、转账和初始代码中的所有状态修改
+        let checkpoint = self.state.borrow_mut().checkpoint();
+        {
+            let mut state = self.state.borrow_mut();
+            state.create_account(&new_contract_address);
+            state.increment_nonce(&new_contract_address);
+            state.transfer(&creator, &new_contract_address, &value);
+        }
+
         // 创建并运行新的EVM实例
-        let mut evm_sub = Evm::init_evm(init_code, txn);
+        let mut evm_sub = self.sub_evm(init_code, txn);
         evm_sub.run();
+        self.gas_used += evm_sub.gas_used.clone();
 
         // 如果evm_sub实例返回错误，栈返回0,表示合约创建失败
         if !evm_sub.success {
+            self.state.borrow_mut().checkpoint_revert(checkpoint);
             self.stack
                 .push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
+            return;
         }
 
-        //更新创建者的nouce
-        creator_account.nonce += BigUint::from(1u8);
-
-        // 存储合约的状态
-        binding.insert(
-            new_contract_address,
-            Account::new(value, zero(), evm_sub.storage, evm_sub.return_data),
-        );
+        // 部署合约代码，初始代码写入的存储已经记录在新账户中
+        let mut state = self.state.borrow_mut();
+        state.set_code(&new_contract_address, evm_sub.return_data);
+        state.checkpoint_commit(checkpoint);
+        drop(state);
 
         // 新创建合约的地址入栈
-        self.stack.push(StackData::new(this_address, 0u8));
+        self.stack.push(StackData::new(
+            new_contract_address.as_bytes().to_vec(),
+            0u8,
+        ));
     }
-    fn create2(&mut self) {
-        if self.stack.len() < 4 {
+
+    /// 检查创建者余额并增加nonce，返回增加前的nonce
+    fn prepare_create(&mut self, value: &BigUint) -> BigUint {
+        let creator = self.txn.get_this_addr();
+        info!("创建者地址{}", creator);
+        let mut state = self.state.borrow_mut();
+        if &state.balance(&creator) < value {
+            panic!("Insufficient balance to create contract!");
+        }
+
+        //更新创建者的nouce
+        state.increment_nonce(&creator)
+    }
+}
+
+impl Contract for Evm {
+    fn create(&mut self) {
+        if self.stack.len() < 3 {
             panic!("Stack underflow");
         }
+        //获取堆栈数据
         let value = get_uint256(self.stack.pop());
         let mem_offset = get_uint256(self.stack.pop());
         let lenght = get_uint256(self.stack.pop());
-        let salt = get_uint256(self.stack.pop());
 
         //拓展内存
         // 获取内存需要的长度
@@ -109,66 +124,40 @@ impl Contract for Evm {
         let init_code = self.memory[mem_offset.to_usize().unwrap()..len].to_vec();
 
         // 检查创建者的余额是否足够
-        let mut binding = get_account_db();
-        info!("创建者地址{}", self.txn.get_this_addr());
-        let creator_account = binding.get_account_mut(self.txn.get_this_addr());
-        if creator_account.balance < value {
-            panic!("Insufficient balance to create contract!");
-        }
-
-        // 扣除创建者指定的金额
-        creator_account.balance -= value.clone();
+        let creator_nonce = self.prepare_create(&value);
 
         // 生成新的合约地址
-        let init_code_hash = hex::encode(keccak256(&init_code));
-        let data_to_hash = "ff".to_string()
-            + &self.txn.get_this_addr().split_off(2)
-            + &hex::encode(salt.to_bytes_be())
-            + &init_code_hash;
-        let new_contract_address_bytes = keccak256(&hex::decode(data_to_hash).unwrap());
-        let new_contract_address = format!(
-            "0x{}",
-            hex::encode(&new_contract_address_bytes[(new_contract_address_bytes.len() - 20)..])
-        );
-        info!("新合约地址{}", new_contract_address);
+        let new_contract_address = create_address(&self.txn.get_this_addr(), &creator_nonce);
 
-        // 构建上下文
-        let txn = Transaction::init(
-            zero(),
-            self.txn.get_gas_limit().clone(),
-            self.txn.get_gas_limit().clone(),
-            new_contract_address.clone(),
-            value.clone(),
-            hex::encode(init_code.clone()),
-            self.txn.get_this_addr(),
-            self.txn.get_origin(),
-            new_contract_address.clone(),
-            zero(),
-            zero(),
-            zero(),
-        );
-
-        let mut evm_create2 = Evm::init_evm(init_code, txn);
-        evm_create2.run();
+        self.create_contract(value, init_code, new_contract_address);
+    }
+    fn create2(&mut self) {
+        if self.stack.len() < 4 {
+            panic!("Stack underflow");
+        }
+        let value = get_uint256(self.stack.pop());
+        let mem_offset = get_uint256(self.stack.pop());
+        let lenght = get_uint256(self.stack.pop());
+        let salt = get_uint256(self.stack.pop());
 
-        // 如果evm_sub实例返回错误，栈返回0,表示合约创建失败
-        if !evm_create2.success {
-            self.stack
-                .push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
+        //拓展内存
+        // 获取内存需要的长度
+        let len = (&mem_offset + &lenght).to_usize().unwrap();
+        if self.memory.len() < len {
+            self.memory.resize(len, 0u8);
         }
 
-        //更新创建者的nouce
-        creator_account.nonce += BigUint::from(1u8);
+        //获取初始代码
+        let init_code = self.memory[mem_offset.to_usize().unwrap()..len].to_vec();
 
-        // 存储合约的状态
-        binding.insert(
-            new_contract_address,
-            Account::new(value, zero(), evm_create2.storage, evm_create2.return_data),
-        );
+        // 检查创建者的余额是否足够
+        self.prepare_create(&value);
 
-        // 新创建合约的地址入栈
-        self.stack
-            .push(StackData::new(new_contract_address_bytes.to_vec(), 0u8));
+        // 生成新的合约地址
+        let new_contract_address =
+            create2_address(&self.txn.get_this_addr(), &salt, &init_code);
+
+        self.create_contract(value, init_code, new_contract_address);
     }
     fn selfdestruct(&mut self) {
         if self.stack.len() < 1 {
@@ -177,28 +166,24 @@ impl Contract for Evm {
 
         // 弹出接收ETH的指定地址
         let raw_recipient = get_uint256(self.stack.pop());
-        let recipient = "0x".to_string() + &hex::encode(raw_recipient.to_bytes_be().to_vec());
+        let recipient = Address::from_word(&raw_recipient);
 
-        let mut db = get_account_db_2();
-        if !db.contains(recipient.clone()) {
-            db.insert(
-                recipient.clone(),
-                Account::new(zero(), zero(), HashMap::new(), vec![]),
-            );
-        }
-        let balance = db.get_account(self.txn.get_this_addr()).balance.clone();
-        let account = db.get_account_mut(recipient);
-        account.balance += balance;
-
-        db.remove(self.txn.get_this_addr());
+        self.state
+            .borrow_mut()
+            .selfdestruct(&self.txn.get_this_addr(), &recipient);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Database;
     use crate::evm::*;
+    use crate::fake_db::{Account, AccountDb};
     use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
     #[test]
     fn test_create() {
         Lazy::force(&INIT_LOG);
@@ -209,12 +194,12 @@ mod tests {
             zero(),
             zero(),
             BigUint::from(1000000u64),
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
             BigUint::from(10u8),
             "".to_string(),
-            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
+            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
+            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
             zero(),
             zero(),
             zero(),
@@ -222,7 +207,36 @@ mod tests {
         // evm::init_log();
         let mut evm_test = Evm::init_evm(bytes, txn);
         evm_test.run();
-        println!("{:?}", get_account_db());
+        println!("{:?}", evm_test.state.borrow().db);
+        // 创建者nonce为1
+        let expected = create_address(
+            &"0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
+            &BigUint::from(1u8),
+        );
+        assert_eq!(
+            Address::from_word(&get_uint256(evm_test.stack.pop())),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_create_address() {
+        let creator = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".parse().unwrap();
+        for (nonce, expected) in [
+            (0u8, "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"),
+            (1, "0x343c43a37d37dff08ae8c4a11544c718abb4fcf8"),
+            (2, "0xf778b86fa74e846c4f0a1fbd1335fe81c00a0c91"),
+        ] {
+            assert_eq!(
+                create_address(&creator, &BigUint::from(nonce)),
+                expected.parse().unwrap()
+            );
+        }
+        // EIP-1014 示例
+        assert_eq!(
+            create2_address(&Address::ZERO, &zero(), &[0x00]),
+            "0x4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38".parse().unwrap()
+        );
     }
 
     #[test]
@@ -234,13 +248,13 @@ mod tests {
         let txn = Transaction::init(
             zero(),
             zero(),
-            zero(),
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
+            BigUint::from(1000000u64),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
             BigUint::from(100000u64),
             "".to_string(),
-            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
+            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
+            "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
+            "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
             zero(),
             zero(),
             zero(),
@@ -248,32 +262,60 @@ mod tests {
         // evm::init_log();
         let mut evm_test = Evm::init_evm(bytes, txn);
         evm_test.run();
-        println!("{:?}", get_account_db());
+        assert!(evm_test.success);
+        // 初始代码63ffffffff6000526004601cf3返回ffffffff，salt为4
+        let expected = create2_address(
+            &"0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
+            &BigUint::from(4u8),
+            &hex::decode("63ffffffff6000526004601cf3").unwrap(),
+        );
+        assert_eq!(
+            Address::from_word(&get_uint256(evm_test.stack.pop())),
+            expected
+        );
+        assert_eq!(evm_test.state.borrow_mut().code(&expected), vec![0xffu8; 4]);
     }
 
     #[test]
     fn test_selfdestruct() {
         Lazy::force(&INIT_LOG);
+        // 销毁当前合约，余额转给0x20
         let excute_codes = "6020ff";
         let bytes = hex::decode(excute_codes).unwrap();
+        let contract: Address = "0x1000000000000000000000000000000000000c42".parse().unwrap();
+        let recipient = Address::from_word(&BigUint::from(0x20u8));
+        let mut db = AccountDb::mock();
+        db.insert(
+            contract,
+            Account::new(
+                BigUint::from(7u8),
+                zero(),
+                HashMap::new(),
+                hex::decode(excute_codes).unwrap(),
+            ),
+        );
+        let db = Rc::new(RefCell::new(db));
         let txn = Transaction::init(
             zero(),
             zero(),
-            zero(),
-            "".to_string(),
             BigUint::from(100000u64),
+            Address::ZERO,
+            zero(),
             "".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
+            contract,
+            contract,
+            contract,
             zero(),
             zero(),
             zero(),
         );
-        // evm::init_log();
-        println!("销毁前{:?}", get_account_db_2());
-        let mut evm_test = Evm::init_evm(bytes, txn);
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn, db.clone());
         evm_test.run();
-        println!("销毁后{:?}", get_account_db_2());
+        assert!(evm_test.success);
+        drop(evm_test);
+        // 合约账户被删除，余额转给接收者
+        let mut db = db.borrow_mut();
+        assert_eq!(db.basic(&contract), None);
+        assert_eq!(db.basic(&recipient).unwrap().balance, BigUint::from(7u8));
     }
 }
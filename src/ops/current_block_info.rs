@@ -1,10 +1,13 @@
+use crate::const_var::BLOCK_HASH_HISTORY;
 use crate::evm::Evm;
 use crate::log_utils::*;
 use crate::ops::traits::*;
+use crate::primitives::B256;
 use crate::stack::StackData;
 use crate::utils::*;
 use num_bigint::BigUint;
 use num_traits::zero;
+use num_traits::ToPrimitive;
 impl CurrentBlockInfo for Evm {
     fn basefee(&mut self) {
         self.stack
@@ -15,11 +18,21 @@ impl CurrentBlockInfo for Evm {
             panic!("stack underflow!");
         }
         let block_num = get_uint256(self.stack.pop());
-        if block_num == *self.current_block.get_number() {
-            self.stack.push(StackData::new(block_num.to_bytes_be(), 0u8));
+        // 只能查询最近256个区块的哈希，当前区块及之后的区块为0
+        let current = self.current_block.get_number();
+        let hash = if block_num < *current
+            && &block_num + BigUint::from(BLOCK_HASH_HISTORY) >= *current
+        {
+            // 区块环境中提供的哈希优先，其次从数据库读取
+            match self.current_block.get_block_hash(&block_num) {
+                Some(hash) => *hash,
+                None => self.state.borrow_mut().block_hash(&block_num),
+            }
         } else {
-            self.stack.push(StackData::new(0u8.to_be_bytes().to_vec(), 0u8));
-        }
+            B256::default()
+        };
+        self.stack
+            .push(StackData::new(hash.as_bytes().to_vec(), 0u8));
     }
     fn chainid(&mut self) {
         self.stack
@@ -27,7 +40,7 @@ impl CurrentBlockInfo for Evm {
     }
     fn coinbase(&mut self) {
         self.stack
-            .push(StackData::new(self.current_block.get_coinbase().to_bytes_be(), 0u8));
+            .push(StackData::new(self.current_block.get_coinbase().as_bytes().to_vec(), 0u8));
     }
     fn gaslimit(&mut self) {
         self.stack
@@ -41,12 +54,162 @@ impl CurrentBlockInfo for Evm {
         self.stack
             .push(StackData::new(self.current_block.get_prevrandao().to_bytes_be(), 0u8));
     }
+    /// 当前执行账户的余额
     fn selfbalance(&mut self) {
+        let balance = self.state.borrow_mut().balance(&self.txn.get_this_addr());
+        self.stack.push(StackData::new(balance.to_bytes_be(), 0u8));
+    }
+    /// 当前交易第index个blob的版本哈希，越界时为0
+    fn blobhash(&mut self) {
+        if self.stack.len() < 1 {
+            panic!("stack underflow!");
+        }
+        let index = get_uint256(self.stack.pop());
+        let hash = index
+            .to_usize()
+            .and_then(|index| self.txn.get_blob_hashes().get(index).copied())
+            .unwrap_or_default();
+        self.stack
+            .push(StackData::new(hash.as_bytes().to_vec(), 0u8));
+    }
+    fn blobbasefee(&mut self) {
         self.stack
-            .push(StackData::new(self.current_block.get_selfbalance().to_bytes_be(), 0u8));
+            .push(StackData::new(self.current_block.get_blob_basefee().to_bytes_be(), 0u8));
     }
     fn timestamp(&mut self) {
         self.stack
             .push(StackData::new(self.current_block.get_timestamp().to_bytes_be(), 0u8));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::evm::*;
+    use crate::primitives::B256;
+    use crate::transaction::Transaction;
+    use num_bigint::BigUint;
+    use once_cell::sync::Lazy;
+
+    #[test]
+    fn test_blobhash() {
+        Lazy::force(&INIT_LOG);
+        let hashes: Vec<B256> = vec![B256::from([1u8; 32]), B256::from([2u8; 32])];
+        let mut txn = Transaction::mock();
+        txn.set_blob_hashes(hashes.clone());
+        // BLOBHASH(1) BLOBHASH(2)
+        let mut evm_test = Evm::init_evm(hex::decode("600149600249").unwrap(), txn);
+        evm_test.run();
+        assert_eq!(evm_test.stack.get(2).data, hashes[1].as_bytes().to_vec());
+        // 越界时为0
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(0u8)
+        );
+    }
+
+    #[test]
+    fn test_blockhash_window() {
+        Lazy::force(&INIT_LOG);
+        // 当前区块号为17871709
+        let current = 17871709u32;
+        let mut db = crate::fake_db::AccountDb::mock();
+        for number in [current - 257, current - 256, current - 1, current] {
+            db.insert_block_hash(
+                BigUint::from(number),
+                B256::from([(number % 251) as u8; 32]),
+            );
+        }
+        let blockhash = |number: u32| {
+            let code = format!("63{:08x}40", number);
+            let mut evm_test = Evm::init_evm_with_db(
+                hex::decode(code).unwrap(),
+                Transaction::mock(),
+                crate::db::shared(db.clone()),
+            );
+            evm_test.run();
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data)
+        };
+        let hash = |number: u32| BigUint::from_bytes_be(&[(number % 251) as u8; 32]);
+        assert_eq!(blockhash(current - 1), hash(current - 1));
+        assert_eq!(blockhash(current - 256), hash(current - 256));
+        // 超出256个区块的窗口
+        assert_eq!(blockhash(current - 257), BigUint::from(0u8));
+        // 当前区块和未来区块为0
+        assert_eq!(blockhash(current), BigUint::from(0u8));
+        assert_eq!(blockhash(current + 1), BigUint::from(0u8));
+    }
+
+    #[test]
+    fn test_selfbalance() {
+        Lazy::force(&INIT_LOG);
+        let mut evm_test = Evm::init_evm(hex::decode("47").unwrap(), Transaction::mock());
+        evm_test.run();
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(100u8)
+        );
+
+        // 余额来自执行账户的当前状态
+        let address = Transaction::mock().get_this_addr();
+        let mut evm_test = Evm::init_evm(hex::decode("47").unwrap(), Transaction::mock());
+        evm_test
+            .state
+            .borrow_mut()
+            .set_balance(&address, BigUint::from(555u32));
+        evm_test.run();
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(555u32)
+        );
+    }
+
+    #[test]
+    fn test_blobhash_in_sub_call() {
+        Lazy::force(&INIT_LOG);
+        let mut txn = Transaction::mock();
+        txn.set_blob_hashes(vec![B256::from([7u8; 32])]);
+        // CALL合约 600049600052 60206000f3（返回BLOBHASH(0)），将返回值读入栈
+        let mut db = crate::fake_db::AccountDb::mock();
+        let contract = "0x100000000000000000000000000000000000b10b".parse().unwrap();
+        db.insert(
+            contract,
+            crate::fake_db::Account::new(
+                BigUint::from(0u8),
+                BigUint::from(0u8),
+                Default::default(),
+                hex::decode("60004960005260206000f3").unwrap(),
+            ),
+        );
+        let bytes = hex::decode(
+            "6020600060006000600073100000000000000000000000000000000000b10b5af1\
+             50600051",
+        )
+        .unwrap();
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn, crate::db::shared(db));
+        evm_test.run();
+        assert_eq!(evm_test.stack.get(1).data, vec![7u8; 32]);
+    }
+
+    #[test]
+    fn test_blobbasefee() {
+        Lazy::force(&INIT_LOG);
+        let mut evm_test = Evm::new(hex::decode("4a").unwrap());
+        evm_test.current_block.set_excess_blob_gas(BigUint::from(0u8));
+        evm_test.run();
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(1u8)
+        );
+
+        let mut evm_test = Evm::new(hex::decode("4a").unwrap());
+        evm_test
+            .current_block
+            .set_excess_blob_gas(BigUint::from(100_000_000u32));
+        evm_test.run();
+        // 1 * e^(100000000 / 3338477) 取整
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(10203769476395u64)
+        );
+    }
+}
@@ -161,6 +161,42 @@ impl Memory for Evm {
         //因为一个十六进制数代表4位所以打印的时候把长度设置成64位长度
         logger.log_memory_store_val(self.memory.clone());
     }
+
+    /// 内存复制指令（EIP-5656）
+    /// 源区间和目标区间可以重叠，结果与先复制到临时缓冲区再写入相同
+    /// ```
+    /// use mini_evm::evm::Evm;
+    /// let excute_codes = "61ff0260005260206000601f5e";
+    /// let bytes = hex::decode(excute_codes).unwrap();
+    /// let mut evm_test = Evm::new(bytes);
+    /// evm_test.run();
+    /// ```
+    fn mcopy(&mut self) {
+        if self.stack.len() < 3 {
+            panic!("Stack underflow");
+        }
+        let dest_offset = get_uint256(self.stack.pop());
+        let offset = get_uint256(self.stack.pop());
+        let size = get_uint256(self.stack.pop());
+        let info_err = format!(
+            "将偏移位置为{:?}的{:?}字节内存复制到{:?}",
+            offset, size, dest_offset
+        );
+        let logger = LogTemplate::new_cal("MCOPY".to_owned(), info_err.to_owned());
+        logger.log_cal();
+        let size = size.to_usize().unwrap();
+        // 长度为0时不扩展内存
+        if size == 0 {
+            return;
+        }
+        let dest_offset = dest_offset.to_usize().unwrap();
+        let offset = offset.to_usize().unwrap();
+        // 每复制一个字（32字节）消耗3 gas
+        self.gas_used += BigUint::from(3 * size.div_ceil(32));
+        self.expand_memory(dest_offset.max(offset) + size);
+        self.memory.copy_within(offset..offset + size, dest_offset);
+        logger.log_memory_store_val(self.memory.clone());
+    }
 }
 
 #[cfg(test)]
@@ -235,4 +271,60 @@ mod tests {
             &hex::encode(evm_test.stack.0.get(0).unwrap().data.to_vec())
         );
     }
+
+    // 内存前32字节写入 0x00 01 02 ... 1f
+    const WORD: &str = "7f000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f600052";
+
+    #[test]
+    fn mcopy_test() {
+        Lazy::force(&INIT_LOG);
+        // 将第一个字复制到偏移0x20处
+        let excute_codes = format!("{}602060006020{}", WORD, "5e");
+        let bytes = hex::decode(excute_codes).unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        let word = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        assert_eq!(hex::encode(&evm_test.memory), word.repeat(2));
+    }
+
+    #[test]
+    fn mcopy_overlap_test() {
+        Lazy::force(&INIT_LOG);
+        // 向前复制 dest=0 offset=1 size=8
+        let bytes = hex::decode(format!("{}600860016000{}", WORD, "5e")).unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        assert_eq!(
+            "010203040506070808090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            hex::encode(&evm_test.memory)
+        );
+
+        // 向后复制 dest=1 offset=0 size=8
+        let bytes = hex::decode(format!("{}600860006001{}", WORD, "5e")).unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        assert_eq!(
+            "000001020304050607090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            hex::encode(&evm_test.memory)
+        );
+    }
+
+    #[test]
+    fn mcopy_gas_test() {
+        Lazy::force(&INIT_LOG);
+        // 空内存中复制1字节到0x20，内存扩展到2个字
+        // 3 * PUSH1 + MCOPY + 复制1个字 + 扩展2个字 = 9 + 3 + 3 + 6
+        let bytes = hex::decode("6001600060205e").unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        assert_eq!(evm_test.memory.len(), 64);
+        assert_eq!(evm_test.gas_used, BigUint::from(21u8));
+
+        // 长度为0时不扩展内存，只消耗静态gas
+        let bytes = hex::decode("6000600060ff5e").unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        assert!(evm_test.memory.is_empty());
+        assert_eq!(evm_test.gas_used, BigUint::from(12u8));
+    }
 }
@@ -55,7 +55,7 @@ impl Other for Evm {
             [mem_offset.to_usize().unwrap()..(mem_offset + length).to_usize().unwrap()]
             .to_vec();
         let log_entry = LogEntry::init(self.txn.get_this_addr(), data, topics);
-        self.logs.push(log_entry);
+        self.state.borrow_mut().log(log_entry);
     }
     /// datacopy指令
     /// 将上一轮计算的结果，复制到内存上
@@ -99,10 +99,12 @@ impl Other for Evm {
     /// datasize指令
     /// 查看返回数据的长度
     /// ```
-    /// let excute_codes = "61aaaa6000526002601ff33d";
+    /// let excute_codes = "61aaaa6000526002601ff3";
     /// let bytes = hex::decode(excute_codes).unwrap();
     /// let mut evm_test = Evm::new(bytes);
     /// evm_test.run();
+    /// evm_test.next_codes(hex::decode("3d").unwrap());
+    /// evm_test.run();
     /// println!("{:?}", evm_test.stack);
     /// ```
     fn return_datasize(&mut self) {
@@ -173,6 +175,7 @@ impl Other for Evm {
 mod tests {
     use super::*;
     use crate::evm::*;
+    use crate::primitives::Address;
     use once_cell::sync::Lazy;
     #[test]
     fn test_sha3() {
@@ -188,11 +191,16 @@ mod tests {
     #[test]
     fn test_log() {
         Lazy::force(&INIT_LOG);
-        let excute_codes = "60aa6000526001601fa0";
+        // LOG1: data为0xaa，topic为0x01
+        let excute_codes = "60aa60005260016001601fa1";
         let bytes = hex::decode(excute_codes).unwrap();
         let mut evm_test = Evm::new(bytes);
         evm_test.run();
-        println!("{:?}", evm_test.logs);
+        assert_eq!(evm_test.logs.len(), 1);
+        let log = &evm_test.logs[0];
+        assert_eq!(log.get_address(), &evm_test.txn.get_this_addr());
+        assert_eq!(log.get_data(), &[0xaa]);
+        assert_eq!(log.get_topics(), &[BigUint::from(1u8)]);
     }
     
     #[test]
@@ -208,12 +216,19 @@ mod tests {
     #[test]
     fn test_returnsize() {
         Lazy::force(&INIT_LOG);
-        let excute_codes = "61aaaa6000526002601ff33d";
+        let excute_codes = "61aaaa6000526002601ff3";
         let bytes = hex::decode(excute_codes).unwrap();
         let mut evm_test = Evm::new(bytes);
         evm_test.run();
         println!("{:?}", evm_test.return_data);
+        // RETURN会停止执行，在下一组指令中读取返回数据长度
+        evm_test.next_codes(hex::decode("3d").unwrap());
+        evm_test.run();
         println!("{:?}", evm_test.stack);
+        assert_eq!(
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            hex::encode(evm_test.stack.get(1).data)
+        );
     }
     
     #[test]
@@ -254,12 +269,12 @@ mod tests {
             zero(),
             BigUint::from(1u8),
             BigUint::from(100u8),
-            "".to_string(),
+            Address::ZERO,
             BigUint::from(10u8),
             "".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
-            "0x1000000000000000000000000000000000000c42".to_string(),
+            "0x1000000000000000000000000000000000000c42".parse().unwrap(),
+            "0x1000000000000000000000000000000000000c42".parse().unwrap(),
+            "0x1000000000000000000000000000000000000c42".parse().unwrap(),
             zero(),
             zero(),
             zero(),
@@ -2,8 +2,6 @@ use crate::log_utils::*;
 use crate::ops::traits::*;
 use crate::utils::*;
 use crate::{evm::Evm, stack::StackData};
-use num_bigint::BigUint;
-use num_traits::{zero, ToPrimitive};
 
 impl Storage for Evm {
     /// 存储读指令
@@ -22,16 +20,13 @@ impl Storage for Evm {
         let info_err = format!("读取键值为{:?}的存储值", key);
         let mut logger = LogTemplate::new_cal("SLOAD".to_owned(), info_err.to_owned());
         logger.log_cal();
-        if self.storage.contains_key(&key) {
-            let value = self.storage.get(&key).unwrap();
-            logger.set_result(get_uint256(value.clone()));
-            self.stack
-                .push(StackData::new(value.0.to_bytes_be(), value.1));
-        } else {
-            logger.set_result(zero());
-            self.stack
-                .push(StackData::new(BigUint::from(0u8).to_bytes_be(), 0));
-        }
+        // 读取当前执行账户的存储，delegatecall时为调用者账户
+        let value = self
+            .state
+            .borrow_mut()
+            .sload(&self.txn.get_this_addr(), &key);
+        logger.set_result(value.clone());
+        self.stack.push(StackData::new(value.to_bytes_be(), 0u8));
         logger.log_store_val();
         logger.log_real_val();
     }
@@ -56,16 +51,83 @@ impl Storage for Evm {
             key.clone(),
             value.clone(),
         );
-        self.storage.insert(get_uint256(key), value);
         logger.log_storage_cal();
-        logger.log_storage_store_val(self.storage.clone());
+        let value = get_uint256(value);
+        logger.set_result(value.clone());
+        // 写入当前执行账户的存储
+        self.state
+            .borrow_mut()
+            .sstore(&self.txn.get_this_addr(), get_uint256(key), value);
+        logger.log_store_val();
+    }
+
+    /// 瞬时存储读指令（EIP-1153）
+    /// ```
+    /// use mini_evm::evm::Evm;
+    /// let excute_codes = "60f160025d60025c";
+    /// let bytes = hex::decode(excute_codes).unwrap();
+    /// let mut evm_test = Evm::new(bytes);
+    /// evm_test.run();
+    /// ```
+    fn tload(&mut self) {
+        if self.stack.len() < 1 {
+            panic!("stack underflow");
+        }
+        let key = get_uint256(self.stack.pop());
+        let info_err = format!("读取键值为{:?}的瞬时存储值", key);
+        let mut logger = LogTemplate::new_cal("TLOAD".to_owned(), info_err.to_owned());
+        logger.log_cal();
+        let value = self
+            .state
+            .borrow_mut()
+            .tload(&self.txn.get_this_addr(), &key);
+        logger.set_result(value.clone());
+        self.stack.push(StackData::new(value.to_bytes_be(), 0u8));
+        logger.log_store_val();
+        logger.log_real_val();
+    }
+
+    /// 瞬时存储写指令（EIP-1153），交易结束时清空，调用失败时回滚
+    /// ```
+    /// use mini_evm::evm::Evm;
+    /// let excute_codes = "60f160025d";
+    /// let bytes = hex::decode(excute_codes).unwrap();
+    /// let mut evm_test = Evm::new(bytes);
+    /// evm_test.run();
+    /// ```
+    fn tstore(&mut self) {
+        if self.stack.len() < 2 {
+            panic!("stack underflow");
+        }
+        let key = self.stack.pop();
+        let value = self.stack.pop();
+        let mut logger = LogTemplate::new_two_cal(
+            "TSTORE".to_owned(),
+            "tstore".to_owned(),
+            key.clone(),
+            value.clone(),
+        );
+        logger.log_storage_cal();
+        let value = get_uint256(value);
+        logger.set_result(value.clone());
+        self.state
+            .borrow_mut()
+            .tstore(&self.txn.get_this_addr(), get_uint256(key), value);
+        logger.log_store_val();
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::db::*;
     use crate::evm::*;
+    use crate::fake_db::{Account, AccountDb};
+    use crate::primitives::Address;
+    use crate::transaction::Transaction;
+    use num_bigint::BigUint;
+    use num_traits::zero;
     use once_cell::sync::Lazy;
+    use std::collections::HashMap;
     #[test]
     fn sstore_test() {
         Lazy::force(&INIT_LOG);
@@ -82,5 +144,162 @@ mod tests {
         let bytes = hex::decode(excute_codes).unwrap();
         let mut evm_test = Evm::new(bytes);
         evm_test.run();
+        assert_eq!(
+            "00000000000000000000000000000000000000000000000000000000000000f1",
+            hex::encode(evm_test.stack.get(1).data)
+        );
+    }
+
+    // 计数器合约: slot0 = slot0 + 1
+    fn counter() -> Address {
+        "0x100000000000000000000000000000000000c0de".parse().unwrap()
+    }
+
+    fn counter_db() -> SharedDb {
+        contract_db("600054600101600055")
+    }
+
+    /// 在counter()地址部署指定代码
+    fn contract_db(code: &str) -> SharedDb {
+        let mut db = AccountDb::mock();
+        db.insert(
+            counter(),
+            Account::new(zero(), zero(), HashMap::new(), hex::decode(code).unwrap()),
+        );
+        shared(db)
+    }
+
+    #[test]
+    fn sstore_persist_test() {
+        Lazy::force(&INIT_LOG);
+        // 两次CALL计数器合约
+        let call = "6000600060006000600173100000000000000000000000000000000000c0de5af150";
+        let bytes = hex::decode(call.repeat(2)).unwrap();
+        let db = counter_db();
+        // 每次带value的CALL额外消耗9000 gas
+        let mut txn = Transaction::mock();
+        txn.set_gas_limit(BigUint::from(100000u32));
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn, db.clone());
+        evm_test.run();
+        assert!(evm_test.success);
+        drop(evm_test);
+        // 虚拟机销毁后存储仍保存在合约账户中
+        assert_eq!(
+            db.borrow_mut().storage(&counter(), &zero()),
+            BigUint::from(2u8)
+        );
+    }
+
+    #[test]
+    fn delegatecall_storage_test() {
+        Lazy::force(&INIT_LOG);
+        let excute_codes = "600060006000600073100000000000000000000000000000000000c0de5af4";
+        let bytes = hex::decode(excute_codes).unwrap();
+        let db = counter_db();
+        let txn = Transaction::mock();
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn.clone(), db.clone());
+        evm_test.run();
+        // delegatecall修改的是调用者账户的存储
+        let mut db = db.borrow_mut();
+        assert_eq!(
+            db.storage(&txn.get_this_addr(), &zero()),
+            BigUint::from(1u8)
+        );
+        assert_eq!(db.storage(&counter(), &zero()), zero());
+    }
+
+    /// 栈上第index个元素的值，1为栈顶
+    fn word(evm: &Evm, index: usize) -> BigUint {
+        BigUint::from_bytes_be(&evm.stack.get(index).data)
+    }
+
+    #[test]
+    fn tstore_tload_test() {
+        Lazy::force(&INIT_LOG);
+        let bytes = hex::decode("60f160025d60025c60035c").unwrap();
+        let mut evm_test = Evm::new(bytes);
+        evm_test.run();
+        assert_eq!(
+            "00000000000000000000000000000000000000000000000000000000000000f1",
+            hex::encode(evm_test.stack.get(2).data)
+        );
+        assert_eq!(word(&evm_test, 1), zero());
+    }
+
+    #[test]
+    fn transient_cleared_test() {
+        Lazy::force(&INIT_LOG);
+        let state = crate::journal::JournaledState::shared(shared(AccountDb::mock()));
+        let bytes = hex::decode("60f160025d").unwrap();
+        let mut evm_test = Evm::init_evm_with_state(bytes, Transaction::mock(), state.clone());
+        evm_test.run();
+        // 下一笔交易读不到上一笔交易写入的瞬时存储
+        let bytes = hex::decode("60025c").unwrap();
+        let mut evm_test = Evm::init_evm_with_state(bytes, Transaction::mock(), state);
+        evm_test.run();
+        assert_eq!(word(&evm_test, 1), zero());
+    }
+
+    #[test]
+    fn transient_revert_test() {
+        Lazy::force(&INIT_LOG);
+        // delegatecall合约后读取slot0的瞬时存储
+        let bytes = hex::decode(
+            "600060006000600073100000000000000000000000000000000000c0de5af460005c",
+        )
+        .unwrap();
+        // 合约写入瞬时存储后正常返回
+        let mut evm_test =
+            Evm::init_evm_with_db(bytes.clone(), Transaction::mock(), contract_db("600160005d"));
+        evm_test.run();
+        assert_eq!(word(&evm_test, 2), BigUint::from(1u8));
+        assert_eq!(word(&evm_test, 1), BigUint::from(1u8));
+
+        // 合约写入瞬时存储后revert，写入被回滚
+        let mut evm_test = Evm::init_evm_with_db(
+            bytes,
+            Transaction::mock(),
+            contract_db("600160005d60006000fd"),
+        );
+        evm_test.run();
+        assert_eq!(word(&evm_test, 2), zero());
+        assert_eq!(word(&evm_test, 1), zero());
+    }
+
+    #[test]
+    fn static_context_test() {
+        Lazy::force(&INIT_LOG);
+        let staticcall =
+            hex::decode("600060006000600073100000000000000000000000000000000000c0de5afa").unwrap();
+        // 静态调用中读取瞬时存储可以成功
+        let mut evm_test = Evm::init_evm_with_db(
+            staticcall.clone(),
+            Transaction::mock(),
+            contract_db("60005c50"),
+        );
+        evm_test.run();
+        assert_eq!(word(&evm_test, 1), BigUint::from(1u8));
+
+        // TSTORE、SSTORE和带value的CALL都会使静态调用失败
+        for code in [
+            "600160005d",
+            "6001600055",
+            "600060006000600060017300000000000000000000000000000000000000005af1",
+        ] {
+            let mut evm_test =
+                Evm::init_evm_with_db(staticcall.clone(), Transaction::mock(), contract_db(code));
+            evm_test.run();
+            assert!(evm_test.success);
+            assert_eq!(word(&evm_test, 1), zero());
+        }
+
+        // 不带value的CALL仍然可以执行
+        let mut evm_test = Evm::init_evm_with_db(
+            staticcall,
+            Transaction::mock(),
+            contract_db("600060006000600060007300000000000000000000000000000000000000005af1"),
+        );
+        evm_test.run();
+        assert_eq!(word(&evm_test, 1), BigUint::from(1u8));
     }
 }
@@ -38,11 +38,14 @@ pub trait Memory {
     fn mload(&mut self);
     fn msize(&mut self);
     fn mstore8(&mut self);
+    fn mcopy(&mut self);
 }
 
 pub trait Storage {
     fn sstore(&mut self);
     fn sload(&mut self);
+    fn tstore(&mut self);
+    fn tload(&mut self);
 }
 
 pub trait ControlFlow {
@@ -62,6 +65,8 @@ pub trait CurrentBlockInfo {
     fn chainid(&mut self);
     fn selfbalance(&mut self);
     fn basefee(&mut self);
+    fn blobhash(&mut self);
+    fn blobbasefee(&mut self);
 }
 
 pub trait AccountTraits {
@@ -103,6 +108,7 @@ pub trait TransactionTraits {
 
 pub trait Call {
     fn call(&mut self);
+    fn callcode(&mut self);
     fn delegatecall(&mut self);
     fn staticcall(&mut self);
 }
@@ -8,7 +8,7 @@ use num_traits::ToPrimitive;
 impl TransactionTraits for Evm {
     fn address(&mut self) {
         self.stack.push(StackData::new(
-            hex::decode(&self.txn.get_this_addr()[2..]).unwrap(),
+            self.txn.get_this_addr().as_bytes().to_vec(),
             0u8,
         ));
     }
@@ -17,7 +17,7 @@ impl TransactionTraits for Evm {
             panic!("Stack underflow");
         }
         let offset = get_uint256(self.stack.pop());
-        let data = hex::decode(&self.txn.get_data()[2..]).unwrap();
+        let data = self.txn.get_calldata();
         let mut result_data = data[offset.to_usize().unwrap()..].to_vec();
         if result_data.len() < 32 {
             result_data.resize(32, 0);
@@ -26,13 +26,13 @@ impl TransactionTraits for Evm {
     }
 
     fn calldatasize(&mut self) {
-        let size = (self.txn.get_data().len() - 2) / 2;
+        let size = self.txn.get_calldata().len();
         self.stack.push(StackData::new(size.to_be_bytes().to_vec(), 0u8));
     }
 
     fn caller(&mut self) {
         self.stack.push(StackData::new(
-            hex::decode(&self.txn.get_caller()[2..]).unwrap(),
+            self.txn.get_caller().as_bytes().to_vec(),
             0u8,
         ));
     }
@@ -54,8 +54,8 @@ impl TransactionTraits for Evm {
             );
         }
 
-        let addr = self.txn.get_this_addr();
-        let codedata = get_account_db().get_account(addr).code.clone();
+        // 当前帧执行的代码，CALLCODE/DELEGATECALL中不是当前账户的代码，初始代码也尚未部署
+        let codedata = self.get_code().to_vec();
         for i in 0..length.to_usize().unwrap() {
             if code_offset.to_usize().unwrap() + i < codedata.len() {
                 self.memory[(code_offset.clone() + BigUint::from(i)).to_usize().unwrap()] =
@@ -64,16 +64,15 @@ impl TransactionTraits for Evm {
         }
     }
     fn codesize(&mut self) {
-        let addr = self.txn.get_this_addr();
-        let result = get_account_db().get_account(addr).code.clone();
-        self.stack.push(StackData::new(result.len().to_be_bytes().to_vec(), 0u8));
+        let size = self.get_code().len();
+        self.stack.push(StackData::new(size.to_be_bytes().to_vec(), 0u8));
     }
     fn gasprice(&mut self) {
         self.stack.push(StackData::new(self.txn.get_gas_price().to_bytes_be(), 0u8));
     }
     fn origin(&mut self) {
         self.stack.push(StackData::new(
-            hex::decode(&self.txn.get_origin()[2..]).unwrap(),
+            self.txn.get_origin().as_bytes().to_vec(),
             0u8,
         ));
     }
@@ -92,7 +91,7 @@ impl TransactionTraits for Evm {
             );
         }
 
-        let calldata = hex::decode(&self.txn.get_data()[2..]).unwrap();
+        let calldata = self.txn.get_calldata();
         for i in 0..length.to_usize().unwrap() {
             if calldata_offset.to_usize().unwrap() + i < calldata.len() {
                 self.memory[(calldata_offset.clone() + BigUint::from(i))
@@ -102,3 +101,69 @@ impl TransactionTraits for Evm {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::evm::*;
+    use crate::fake_db::{Account, AccountDb};
+    use crate::primitives::Address;
+    use crate::transaction::Transaction;
+    use crate::utils::get_uint256;
+    use num_bigint::BigUint;
+    use num_traits::zero;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_codesize_in_sub_frames() {
+        Lazy::force(&INIT_LOG);
+        // 被调用合约: slot0 = CODESIZE，代码长5字节，调用者的代码60006000长4字节
+        let callee: Address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let mut db = AccountDb::mock();
+        db.insert(
+            callee,
+            Account::new(
+                zero(),
+                zero(),
+                HashMap::new(),
+                hex::decode("3860005500").unwrap(),
+            ),
+        );
+        let db = Rc::new(RefCell::new(db));
+        let bytes = hex::decode(
+            "600060006000600060007310000000000000000000000000000000\
+             0000c0de5af2",
+        )
+        .unwrap();
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        assert!(evm_test.success);
+        let this_addr = Transaction::mock().get_this_addr();
+        assert_eq!(
+            evm_test.state.borrow_mut().sload(&this_addr, &zero()),
+            BigUint::from(5u8)
+        );
+
+        // 初始代码同样为3860005500，执行时新合约还没有代码
+        let bytes = hex::decode("6438600055006000526005601b6000f0").unwrap();
+        let mut txn = Transaction::mock();
+        txn.set_gas_limit(BigUint::from(100000u32));
+        let mut evm_test = Evm::init_evm(bytes, txn);
+        evm_test.run();
+        assert!(evm_test.success);
+        let created = Address::from_word(&get_uint256(evm_test.stack.pop()));
+        assert_ne!(created, Address::ZERO);
+        assert_eq!(
+            evm_test.state.borrow_mut().sload(&created, &zero()),
+            BigUint::from(5u8)
+        );
+        // 顶层帧中为正在执行的字节码长度
+        let mut evm_test = Evm::init_evm(hex::decode("38").unwrap(), Transaction::mock());
+        evm_test.run();
+        assert_eq!(get_uint256(evm_test.stack.pop()), BigUint::from(1u8));
+    }
+}
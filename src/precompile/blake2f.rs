@@ -0,0 +1,165 @@
+/// 0x09 BLAKE2f（EIP-152）
+/// 输入必须为213字节：rounds(4，大端) || h(64) || m(128) || t(16) || f(1)，
+/// h、m、t均为小端u64，f只能是0或1。每轮消耗1 gas，返回压缩后的h。
+use super::{charge, PrecompileError, PrecompileOutput, PrecompileResult};
+
+const INPUT_LEN: usize = 213;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// 混合函数G
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// BLAKE2b的压缩函数F，轮数可以是任意值
+pub fn compress(rounds: u32, h: &mut [u64; 8], m: &[u64; 16], t: [u64; 2], last: bool) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+    for round in 0..rounds as usize {
+        let s = &SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+fn read_u64(input: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(input[offset..offset + 8].try_into().unwrap())
+}
+
+pub fn blake2f(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    if input.len() != INPUT_LEN {
+        return Err(PrecompileError::InvalidInput(format!(
+            "blake2f input length must be {}, got {}",
+            INPUT_LEN,
+            input.len()
+        )));
+    }
+    let last = match input[212] {
+        0 => false,
+        1 => true,
+        flag => {
+            return Err(PrecompileError::InvalidInput(format!(
+                "invalid final block flag: {}",
+                flag
+            )))
+        }
+    };
+    let rounds = u32::from_be_bytes(input[..4].try_into().unwrap());
+    let gas_used = charge(rounds as u64, gas_limit)?;
+
+    let mut h = [0u64; 8];
+    for (i, word) in h.iter_mut().enumerate() {
+        *word = read_u64(input, 4 + i * 8);
+    }
+    let mut m = [0u64; 16];
+    for (i, word) in m.iter_mut().enumerate() {
+        *word = read_u64(input, 68 + i * 8);
+    }
+    let t = [read_u64(input, 196), read_u64(input, 204)];
+    compress(rounds, &mut h, &m, t, last);
+
+    let output = h.iter().flat_map(|word| word.to_le_bytes()).collect();
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EIP-152的测试输入，对"abc"做压缩
+    fn input(rounds: &str, flag: &str) -> Vec<u8> {
+        hex::decode(format!(
+            "{}48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5\
+             d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b\
+             6162630000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             03000000000000000000000000000000{}",
+            rounds, flag
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blake2f() {
+        let output = blake2f(&input("0000000c", "01"), 12).unwrap();
+        assert_eq!(
+            hex::encode(&output.output),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(output.gas_used, 12);
+
+        let output = blake2f(&input("0000000c", "00"), 12).unwrap();
+        assert_eq!(
+            hex::encode(&output.output),
+            "75ab69d3190a562c51aef8d88f1c2775876944407270c42c9844252c26d28752\
+             98743e7f6d5ea2f2d3e8d226039cd31b4e426ac4f2d3d666a610c2116fde4735"
+        );
+
+        // 0轮时不消耗gas
+        let output = blake2f(&input("00000000", "01"), 0).unwrap();
+        assert_eq!(output.gas_used, 0);
+        assert_eq!(
+            blake2f(&input("0000000c", "01"), 11),
+            Err(PrecompileError::OutOfGas)
+        );
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let valid = input("0000000c", "01");
+        assert!(blake2f(&valid[..212], u64::MAX).is_err());
+        assert!(blake2f(&[valid.clone(), vec![0]].concat(), u64::MAX).is_err());
+        assert!(matches!(
+            blake2f(&input("0000000c", "02"), u64::MAX),
+            Err(PrecompileError::InvalidInput(_))
+        ));
+    }
+}
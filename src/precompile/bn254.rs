@@ -0,0 +1,192 @@
+/// alt_bn128（BN254）曲线预编译合约
+/// 0x06 ECADD，0x07 ECMUL，0x08 配对检查（EIP-196、EIP-197），gas在Istanbul时按EIP-1108下调。
+/// 坐标均为32字节大端整数，(0, 0)表示无穷远点；G2点按 x虚部、x实部、y虚部、y实部 编码。
+use substrate_bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, Gt, G1, G2};
+
+use super::{charge, PrecompileError, PrecompileOutput, PrecompileResult};
+
+// Byzantium的gas
+pub const BYZANTIUM_ADD_GAS: u64 = 500;
+pub const BYZANTIUM_MUL_GAS: u64 = 40000;
+pub const BYZANTIUM_PAIRING_BASE_GAS: u64 = 100000;
+pub const BYZANTIUM_PAIRING_PER_PAIR_GAS: u64 = 80000;
+// Istanbul的gas（EIP-1108）
+pub const ISTANBUL_ADD_GAS: u64 = 150;
+pub const ISTANBUL_MUL_GAS: u64 = 6000;
+pub const ISTANBUL_PAIRING_BASE_GAS: u64 = 45000;
+pub const ISTANBUL_PAIRING_PER_PAIR_GAS: u64 = 34000;
+
+// 配对检查中每组输入的长度：G1(64) + G2(128)
+const PAIR_LEN: usize = 192;
+
+/// 取input[offset..offset + 32]，超出输入的部分补0
+fn read_word(input: &[u8], offset: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    if offset < input.len() {
+        let end = input.len().min(offset + 32);
+        word[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    word
+}
+
+fn read_fq(input: &[u8], offset: usize) -> Result<Fq, PrecompileError> {
+    Fq::from_slice(&read_word(input, offset))
+        .map_err(|_| PrecompileError::InvalidInput("coordinate not in field".into()))
+}
+
+/// 读取G1点并检查是否在曲线上
+fn read_g1(input: &[u8], offset: usize) -> Result<G1, PrecompileError> {
+    let x = read_fq(input, offset)?;
+    let y = read_fq(input, offset + 32)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1::zero());
+    }
+    AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput("G1 point not on curve".into()))
+}
+
+/// 读取G2点并检查是否在曲线及子群上
+fn read_g2(input: &[u8], offset: usize) -> Result<G2, PrecompileError> {
+    let x_imaginary = read_fq(input, offset)?;
+    let x_real = read_fq(input, offset + 32)?;
+    let y_imaginary = read_fq(input, offset + 64)?;
+    let y_real = read_fq(input, offset + 96)?;
+    let x = Fq2::new(x_real, x_imaginary);
+    let y = Fq2::new(y_real, y_imaginary);
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2::zero());
+    }
+    AffineG2::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput("G2 point not on curve".into()))
+}
+
+/// 将G1点编码为64字节，无穷远点为全0
+fn encode_g1(point: G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+    output
+}
+
+pub fn ec_add(input: &[u8], gas_limit: u64, cost: u64) -> PrecompileResult {
+    let gas_used = charge(cost, gas_limit)?;
+    let p1 = read_g1(input, 0)?;
+    let p2 = read_g1(input, 64)?;
+    Ok(PrecompileOutput::new(gas_used, encode_g1(p1 + p2)))
+}
+
+pub fn ec_mul(input: &[u8], gas_limit: u64, cost: u64) -> PrecompileResult {
+    let gas_used = charge(cost, gas_limit)?;
+    let point = read_g1(input, 0)?;
+    // 标量可以是任意256位整数
+    let scalar = Fr::from_slice(&read_word(input, 64)).unwrap();
+    Ok(PrecompileOutput::new(gas_used, encode_g1(point * scalar)))
+}
+
+/// 检查 e(a1, b1) * e(a2, b2) * ... == 1，成立时返回1，否则返回0
+pub fn ec_pairing(input: &[u8], gas_limit: u64, base: u64, per_pair: u64) -> PrecompileResult {
+    if !input.len().is_multiple_of(PAIR_LEN) {
+        return Err(PrecompileError::InvalidInput(
+            "pairing input length must be a multiple of 192".into(),
+        ));
+    }
+    let pairs = input.len() / PAIR_LEN;
+    let gas_used = charge(base + per_pair * pairs as u64, gas_limit)?;
+    let mut points = Vec::with_capacity(pairs);
+    for i in 0..pairs {
+        let offset = i * PAIR_LEN;
+        points.push((read_g1(input, offset)?, read_g2(input, offset + 64)?));
+    }
+    let success = points.is_empty() || substrate_bn::pairing_batch(&points) == Gt::one();
+    let mut output = vec![0u8; 32];
+    output[31] = success as u8;
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // G1生成元 (1, 2) 及其相反数 (1, p - 2)
+    const G1_GEN: &str = "0000000000000000000000000000000000000000000000000000000000000001\
+                          0000000000000000000000000000000000000000000000000000000000000002";
+    const G1_NEG: &str = "0000000000000000000000000000000000000000000000000000000000000001\
+                          30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45";
+    // 2 * G1
+    const G1_DOUBLE: &str = "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+                             15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4";
+    // G2生成元
+    const G2_GEN: &str = "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+                          1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+                          090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+                          12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa";
+
+    fn decode(parts: &[&str]) -> Vec<u8> {
+        hex::decode(parts.concat()).unwrap()
+    }
+
+    #[test]
+    fn test_ec_add() {
+        let output = ec_add(&decode(&[G1_GEN, G1_GEN]), 150, ISTANBUL_ADD_GAS).unwrap();
+        assert_eq!(hex::encode(&output.output), G1_DOUBLE);
+        assert_eq!(output.gas_used, 150);
+        // P + (-P) 为无穷远点
+        let output = ec_add(&decode(&[G1_GEN, G1_NEG]), 150, ISTANBUL_ADD_GAS).unwrap();
+        assert_eq!(output.output, vec![0u8; 64]);
+        // 空输入即两个无穷远点相加
+        let output = ec_add(&[], 150, ISTANBUL_ADD_GAS).unwrap();
+        assert_eq!(output.output, vec![0u8; 64]);
+        // 不在曲线上的点
+        let mut invalid = decode(&[G1_GEN]);
+        invalid[63] = 3;
+        assert!(matches!(
+            ec_add(&invalid, 150, ISTANBUL_ADD_GAS),
+            Err(PrecompileError::InvalidInput(_))
+        ));
+        assert_eq!(
+            ec_add(&[], 499, BYZANTIUM_ADD_GAS),
+            Err(PrecompileError::OutOfGas)
+        );
+    }
+
+    #[test]
+    fn test_ec_mul() {
+        let scalar = format!("{:064x}", 2);
+        let output = ec_mul(&decode(&[G1_GEN, &scalar]), 6000, ISTANBUL_MUL_GAS).unwrap();
+        assert_eq!(hex::encode(&output.output), G1_DOUBLE);
+        // 乘以群的阶得到无穷远点
+        let order = "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+        let output = ec_mul(&decode(&[G1_GEN, order]), 6000, ISTANBUL_MUL_GAS).unwrap();
+        assert_eq!(output.output, vec![0u8; 64]);
+    }
+
+    #[test]
+    fn test_ec_pairing() {
+        let pairing = |input: &[u8]| {
+            ec_pairing(
+                input,
+                u64::MAX,
+                ISTANBUL_PAIRING_BASE_GAS,
+                ISTANBUL_PAIRING_PER_PAIR_GAS,
+            )
+            .unwrap()
+        };
+        // e(G1, G2) * e(-G1, G2) = 1
+        let output = pairing(&decode(&[G1_GEN, G2_GEN, G1_NEG, G2_GEN]));
+        assert_eq!(output.output[31], 1);
+        assert_eq!(output.gas_used, 45000 + 2 * 34000);
+        // e(G1, G2) != 1
+        let output = pairing(&decode(&[G1_GEN, G2_GEN]));
+        assert_eq!(output.output[31], 0);
+        // 空输入视为成立
+        let output = pairing(&[]);
+        assert_eq!(output.output[31], 1);
+        assert_eq!(output.gas_used, 45000);
+        // 长度不是192的倍数
+        assert!(ec_pairing(&[0u8; 191], u64::MAX, 0, 0).is_err());
+    }
+}
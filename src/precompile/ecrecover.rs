@@ -0,0 +1,143 @@
+/// 0x01 ecrecover
+/// 输入为 hash(32) || v(32) || r(32) || s(32)，不足128字节时右侧补0，
+/// 返回签名者地址（左侧补0到32字节），输入无效时返回空输出，固定消耗3000 gas。
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+
+use super::{charge, PrecompileOutput, PrecompileResult};
+use crate::utils::keccak256;
+
+const ECRECOVER_GAS: u64 = 3000;
+
+/// 恢复签名者地址，v为27或28
+/// 以太坊允许s取高位值，此时先将s规范化为n - s并翻转recovery id
+pub fn recover_address(hash: &[u8; 32], v: u8, r: &[u8; 32], s: &[u8; 32]) -> Option<[u8; 20]> {
+    // 只接受27和28，29/30对应的x坐标溢出情形不被以太坊支持
+    if v != 27 && v != 28 {
+        return None;
+    }
+    let recovery_id = RecoveryId::new(v == 28, false);
+    let signature = Signature::from_scalars(*r, *s).ok()?;
+    let (signature, recovery_id) = match signature.normalize_s() {
+        Some(normalized) => (
+            normalized,
+            RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced()),
+        ),
+        None => (signature, recovery_id),
+    };
+    let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id).ok()?;
+    let public_key = key.to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Some(address)
+}
+
+pub fn ecrecover(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = charge(ECRECOVER_GAS, gas_limit)?;
+    let mut padded = [0u8; 128];
+    let len = input.len().min(128);
+    padded[..len].copy_from_slice(&input[..len]);
+
+    // v必须是32字节的27或28
+    let (v_word, rest) = padded[32..].split_at(32);
+    if v_word[..31].iter().any(|&byte| byte != 0) {
+        return Ok(PrecompileOutput::new(gas_used, Vec::new()));
+    }
+    let hash: [u8; 32] = padded[..32].try_into().unwrap();
+    let r: [u8; 32] = rest[..32].try_into().unwrap();
+    let s: [u8; 32] = rest[32..64].try_into().unwrap();
+    let output = match recover_address(&hash, v_word[31], &r, &s) {
+        Some(address) => {
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&address);
+            output
+        }
+        None => Vec::new(),
+    };
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precompile::PrecompileError;
+    use k256::ecdsa::SigningKey;
+    use k256::elliptic_curve::ops::Neg;
+
+    // 私钥1对应的地址
+    const ADDRESS: &str = "0000000000000000000000007e5f4552091a69125d5dfcb7b8c2659029395bdf";
+
+    /// 用私钥1签名，返回ecrecover的输入
+    fn signed_input(hash: &[u8; 32]) -> (Vec<u8>, Signature, RecoveryId) {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = SigningKey::from_bytes(&secret.into()).unwrap();
+        let (signature, recovery_id) = key.sign_prehash_recoverable(hash).unwrap();
+        (
+            encode(hash, 27 + recovery_id.to_byte(), &signature),
+            signature,
+            recovery_id,
+        )
+    }
+
+    fn encode(hash: &[u8; 32], v: u8, signature: &Signature) -> Vec<u8> {
+        let mut input = hash.to_vec();
+        input.extend_from_slice(&[0u8; 31]);
+        input.push(v);
+        input.extend_from_slice(&signature.to_bytes());
+        input
+    }
+
+    #[test]
+    fn test_ecrecover() {
+        let hash = keccak256(b"mini-evm");
+        let (input, signature, recovery_id) = signed_input(&hash);
+        let output = ecrecover(&input, 3000).unwrap();
+        assert_eq!(hex::encode(&output.output), ADDRESS);
+        assert_eq!(output.gas_used, 3000);
+
+        // 高位s同样可以恢复出相同地址
+        let high_s =
+            Signature::from_scalars(signature.r().to_bytes(), signature.s().neg().to_bytes())
+                .unwrap();
+        let flipped = 27 + (recovery_id.to_byte() ^ 1);
+        let output = ecrecover(&encode(&hash, flipped, &high_s), 3000).unwrap();
+        assert_eq!(hex::encode(&output.output), ADDRESS);
+
+        assert_eq!(ecrecover(&input, 2999), Err(PrecompileError::OutOfGas));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let hash = keccak256(b"mini-evm");
+        let (input, _, _) = signed_input(&hash);
+        // v不是27或28
+        for v in [0u8, 26, 29] {
+            let mut invalid = input.clone();
+            invalid[63] = v;
+            assert!(ecrecover(&invalid, 3000).unwrap().output.is_empty());
+        }
+        // v的高位字节不为0
+        let mut invalid = input.clone();
+        invalid[32] = 1;
+        assert!(ecrecover(&invalid, 3000).unwrap().output.is_empty());
+        // r为0
+        let mut invalid = input.clone();
+        invalid[64..96].fill(0);
+        assert!(ecrecover(&invalid, 3000).unwrap().output.is_empty());
+        // v为29或30时即使r很小（r + n仍小于域的模）也不能恢复地址
+        for v in [29u8, 30] {
+            for r in 1u8..=16 {
+                let mut invalid = input.clone();
+                invalid[63] = v;
+                invalid[64..96].fill(0);
+                invalid[95] = r;
+                assert!(ecrecover(&invalid, 3000).unwrap().output.is_empty());
+            }
+        }
+        // 空输入同样消耗3000 gas
+        let output = ecrecover(&[], 3000).unwrap();
+        assert!(output.output.is_empty());
+        assert_eq!(output.gas_used, 3000);
+    }
+}
@@ -0,0 +1,50 @@
+/// 哈希类预编译合约
+/// 0x02 SHA-256，0x03 RIPEMD-160（结果左侧补0到32字节）
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use super::{charge, linear_cost, PrecompileOutput, PrecompileResult};
+
+pub fn sha256(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = charge(linear_cost(input.len(), 60, 12), gas_limit)?;
+    Ok(PrecompileOutput::new(
+        gas_used,
+        Sha256::digest(input).to_vec(),
+    ))
+}
+
+pub fn ripemd160(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = charge(linear_cost(input.len(), 600, 120), gas_limit)?;
+    let mut output = vec![0u8; 12];
+    output.extend_from_slice(&Ripemd160::digest(input));
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precompile::PrecompileError;
+
+    #[test]
+    fn test_sha256() {
+        let output = sha256(b"", 100).unwrap();
+        assert_eq!(
+            hex::encode(output.output),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(output.gas_used, 60);
+        assert_eq!(sha256(&[0u8; 33], 100).unwrap().gas_used, 84);
+        assert_eq!(sha256(&[0u8; 33], 83), Err(PrecompileError::OutOfGas));
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        let output = ripemd160(b"abc", 1000).unwrap();
+        assert_eq!(
+            hex::encode(output.output),
+            "0000000000000000000000008eb208f7e05d987a9b044a8e98c6b087f15a0bfc"
+        );
+        assert_eq!(output.gas_used, 720);
+        assert_eq!(ripemd160(b"abc", 719), Err(PrecompileError::OutOfGas));
+    }
+}
@@ -0,0 +1,7 @@
+/// 0x04 identity，原样返回输入
+use super::{charge, linear_cost, PrecompileOutput, PrecompileResult};
+
+pub fn identity(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = charge(linear_cost(input.len(), 15, 3), gas_limit)?;
+    Ok(PrecompileOutput::new(gas_used, input.to_vec()))
+}
@@ -0,0 +1,181 @@
+/// 0x0a 点求值预编译合约（EIP-4844）
+/// 输入为 versioned_hash(32) | z(32) | y(32) | commitment(48) | proof(48)，
+/// 验证commitment对应的多项式在z处的值为y，成功时返回FIELD_ELEMENTS_PER_BLOB和BLS_MODULUS
+use std::path::Path;
+use std::rc::Rc;
+
+use c_kzg::{Bytes32, Bytes48, KzgSettings};
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+
+use super::{charge, Precompile, PrecompileError, PrecompileOutput, PrecompileResult};
+
+pub const POINT_EVALUATION_GAS: u64 = 50000;
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+pub const FIELD_ELEMENTS_PER_BLOB: u64 = 4096;
+pub const BLS_MODULUS: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// 以太坊主网的可信设置，编译时嵌入，不依赖运行时的文件路径
+pub const DEFAULT_TRUSTED_SETUP: &str = include_str!("../../fixtures/trusted_setup.txt");
+
+// 默认可信设置只在第一次调用时解析
+static DEFAULT_SETTINGS: OnceCell<KzgSettings> = OnceCell::new();
+
+/// 从文件加载可信设置
+pub fn load_trusted_setup(path: &Path) -> Result<KzgSettings, c_kzg::Error> {
+    KzgSettings::load_trusted_setup_file(path, 0)
+}
+
+/// 嵌入的默认可信设置
+pub fn default_settings() -> Result<&'static KzgSettings, c_kzg::Error> {
+    DEFAULT_SETTINGS
+        .get_or_try_init(|| KzgSettings::parse_kzg_trusted_setup(DEFAULT_TRUSTED_SETUP, 0))
+}
+
+/// commitment对应的versioned hash：版本号加sha256(commitment)的后31字节
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+/// 使用默认可信设置的点求值预编译合约
+pub fn point_evaluation(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = charge(POINT_EVALUATION_GAS, gas_limit)?;
+    let settings = default_settings().map_err(|err| {
+        PrecompileError::InvalidInput(format!("trusted setup unavailable: {:?}", err))
+    })?;
+    verify(input, gas_used, settings)
+}
+
+/// 使用指定可信设置的点求值预编译合约
+pub fn point_evaluation_with(settings: Rc<KzgSettings>) -> Precompile {
+    Rc::new(move |input: &[u8], gas_limit: u64| {
+        let gas_used = charge(POINT_EVALUATION_GAS, gas_limit)?;
+        verify(input, gas_used, &settings)
+    })
+}
+
+fn verify(input: &[u8], gas_used: u64, settings: &KzgSettings) -> PrecompileResult {
+    if input.len() != 192 {
+        return Err(PrecompileError::InvalidInput(format!(
+            "point evaluation input must be 192 bytes, got {}",
+            input.len()
+        )));
+    }
+    let commitment = &input[96..144];
+    if kzg_to_versioned_hash(commitment) != input[..32] {
+        return Err(PrecompileError::InvalidInput(
+            "versioned hash mismatch".to_string(),
+        ));
+    }
+    let to_bytes32 = |bytes: &[u8]| -> Bytes32 {
+        let mut word = [0u8; 32];
+        word.copy_from_slice(bytes);
+        word.into()
+    };
+    let to_bytes48 = |bytes: &[u8]| -> Bytes48 {
+        let mut point = [0u8; 48];
+        point.copy_from_slice(bytes);
+        point.into()
+    };
+    let valid = settings
+        .verify_kzg_proof(
+            &to_bytes48(commitment),
+            &to_bytes32(&input[32..64]),
+            &to_bytes32(&input[64..96]),
+            &to_bytes48(&input[144..192]),
+        )
+        .map_err(|err| PrecompileError::InvalidInput(format!("{:?}", err)))?;
+    if !valid {
+        return Err(PrecompileError::InvalidInput(
+            "invalid kzg proof".to_string(),
+        ));
+    }
+    let mut output = vec![0u8; 24];
+    output.extend_from_slice(&FIELD_ELEMENTS_PER_BLOB.to_be_bytes());
+    output.extend_from_slice(&BLS_MODULUS);
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use c_kzg::{Blob, BYTES_PER_BLOB};
+
+    /// 构造一个非零blob的commitment和z处的证明，返回预编译合约的输入
+    fn proof_input(settings: &KzgSettings) -> Vec<u8> {
+        let mut blob = [0u8; BYTES_PER_BLOB];
+        for (i, element) in blob.chunks_mut(32).take(16).enumerate() {
+            element[31] = i as u8 + 1;
+        }
+        let blob = Blob::new(blob);
+        let commitment = settings.blob_to_kzg_commitment(&blob).unwrap().to_bytes();
+        let z: Bytes32 = [0x11u8; 32].into();
+        let (proof, y) = settings.compute_kzg_proof(&blob, &z).unwrap();
+
+        let mut input = kzg_to_versioned_hash(commitment.as_slice()).to_vec();
+        input.extend_from_slice(z.as_slice());
+        input.extend_from_slice(y.as_slice());
+        input.extend_from_slice(commitment.as_slice());
+        input.extend_from_slice(proof.to_bytes().as_slice());
+        input
+    }
+
+    #[test]
+    fn test_point_evaluation() {
+        let settings = load_trusted_setup(Path::new("fixtures/trusted_setup.txt")).unwrap();
+        let input = proof_input(&settings);
+        let output = point_evaluation(&input, 60000).unwrap();
+        assert_eq!(output.gas_used, POINT_EVALUATION_GAS);
+        assert_eq!(
+            hex::encode(output.output),
+            "0000000000000000000000000000000000000000000000000000000000001000\
+             73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001"
+        );
+        assert_eq!(
+            point_evaluation_with(Rc::new(settings))(&input, 50000)
+                .unwrap()
+                .gas_used,
+            POINT_EVALUATION_GAS
+        );
+        assert_eq!(
+            point_evaluation(&input, 49999),
+            Err(PrecompileError::OutOfGas)
+        );
+    }
+
+    #[test]
+    fn test_point_evaluation_invalid() {
+        let settings = load_trusted_setup(Path::new("fixtures/trusted_setup.txt")).unwrap();
+        let input = proof_input(&settings);
+        assert!(point_evaluation(&input[..191], 50000).is_err());
+
+        // y值错误
+        let mut wrong_y = input.clone();
+        wrong_y[95] ^= 1;
+        assert!(point_evaluation(&wrong_y, 50000).is_err());
+
+        // versioned hash版本号错误
+        let mut wrong_version = input.clone();
+        wrong_version[0] = 0x02;
+        assert_eq!(
+            point_evaluation(&wrong_version, 50000),
+            Err(PrecompileError::InvalidInput(
+                "versioned hash mismatch".to_string()
+            ))
+        );
+
+        // 零多项式：commitment和proof均为无穷远点
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0xc0;
+        let mut zero = kzg_to_versioned_hash(&infinity).to_vec();
+        zero.extend_from_slice(&[0u8; 64]);
+        zero.extend_from_slice(&infinity);
+        zero.extend_from_slice(&infinity);
+        assert!(point_evaluation(&zero, 50000).is_ok());
+    }
+}
@@ -0,0 +1,313 @@
+/// 预编译合约
+/// CALL系列指令在执行目标代码之前先查询预编译表，目标地址为预编译合约时直接调用对应的函数，
+/// 每个预编译合约根据输入计算所需gas，gas不足或输入无效时调用失败并消耗全部gas。
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::primitives::Address;
+
+pub mod blake2f;
+pub mod bn254;
+pub mod ecrecover;
+pub mod hash;
+pub mod identity;
+pub mod kzg;
+pub mod modexp;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrecompileError {
+    OutOfGas,
+    // 输入格式错误
+    InvalidInput(String),
+}
+
+impl fmt::Display for PrecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrecompileError::OutOfGas => write!(f, "out of gas"),
+            PrecompileError::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PrecompileError {}
+
+/// 预编译合约执行结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrecompileOutput {
+    pub gas_used: u64,
+    pub output: Vec<u8>,
+}
+
+impl PrecompileOutput {
+    pub fn new(gas_used: u64, output: Vec<u8>) -> Self {
+        Self { gas_used, output }
+    }
+}
+
+pub type PrecompileResult = Result<PrecompileOutput, PrecompileError>;
+
+/// 预编译合约函数，参数为输入数据和可用gas
+pub type Precompile = Rc<dyn Fn(&[u8], u64) -> PrecompileResult>;
+
+/// 基础gas加按字（32字节）计算的gas
+pub fn linear_cost(len: usize, base: u64, word: u64) -> u64 {
+    base + word * len.div_ceil(32) as u64
+}
+
+/// 检查gas是否足够
+pub fn charge(cost: u64, gas_limit: u64) -> Result<u64, PrecompileError> {
+    if cost > gas_limit {
+        return Err(PrecompileError::OutOfGas);
+    }
+    Ok(cost)
+}
+
+/// 预编译合约地址，低位为编号
+pub fn precompile_address(index: u8) -> Address {
+    let mut bytes = [0u8; 20];
+    bytes[19] = index;
+    Address::new(bytes)
+}
+
+/// 影响预编译合约集合及其gas计算的硬分叉
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hardfork {
+    Byzantium,
+    Istanbul,
+    Berlin,
+    Cancun,
+}
+
+/// 预编译合约表
+#[derive(Clone, Default)]
+pub struct Precompiles {
+    inner: HashMap<Address, Precompile>,
+}
+
+impl fmt::Debug for Precompiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut addresses: Vec<_> = self.inner.keys().collect();
+        addresses.sort();
+        f.debug_set().entries(addresses).finish()
+    }
+}
+
+impl Precompiles {
+    /// 空的预编译表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前支持的全部预编译合约
+    /// ```
+    /// use mini_evm::precompile::{precompile_address, Precompiles};
+    /// let precompiles = Precompiles::latest();
+    /// let output = precompiles.get(&precompile_address(4)).unwrap()(&[1, 2, 3], 100).unwrap();
+    /// assert_eq!(output.output, vec![1, 2, 3]);
+    /// assert_eq!(output.gas_used, 18);
+    /// ```
+    pub fn latest() -> Self {
+        Self::hardfork(Hardfork::Cancun)
+    }
+
+    /// 指定硬分叉下的预编译合约
+    pub fn hardfork(hardfork: Hardfork) -> Self {
+        let mut precompiles = Self::new();
+        precompiles.register(precompile_address(1), Rc::new(ecrecover::ecrecover));
+        precompiles.register(precompile_address(2), Rc::new(hash::sha256));
+        precompiles.register(precompile_address(3), Rc::new(hash::ripemd160));
+        precompiles.register(precompile_address(4), Rc::new(identity::identity));
+        if hardfork >= Hardfork::Berlin {
+            precompiles.register(precompile_address(5), Rc::new(modexp::modexp_eip2565));
+        } else {
+            precompiles.register(precompile_address(5), Rc::new(modexp::modexp_eip198));
+        }
+        let (add, mul, pairing_base, pairing_per_pair) = if hardfork >= Hardfork::Istanbul {
+            (
+                bn254::ISTANBUL_ADD_GAS,
+                bn254::ISTANBUL_MUL_GAS,
+                bn254::ISTANBUL_PAIRING_BASE_GAS,
+                bn254::ISTANBUL_PAIRING_PER_PAIR_GAS,
+            )
+        } else {
+            (
+                bn254::BYZANTIUM_ADD_GAS,
+                bn254::BYZANTIUM_MUL_GAS,
+                bn254::BYZANTIUM_PAIRING_BASE_GAS,
+                bn254::BYZANTIUM_PAIRING_PER_PAIR_GAS,
+            )
+        };
+        precompiles.register(
+            precompile_address(6),
+            Rc::new(move |input: &[u8], gas_limit: u64| bn254::ec_add(input, gas_limit, add)),
+        );
+        precompiles.register(
+            precompile_address(7),
+            Rc::new(move |input: &[u8], gas_limit: u64| bn254::ec_mul(input, gas_limit, mul)),
+        );
+        precompiles.register(
+            precompile_address(8),
+            Rc::new(move |input: &[u8], gas_limit: u64| {
+                bn254::ec_pairing(input, gas_limit, pairing_base, pairing_per_pair)
+            }),
+        );
+        if hardfork >= Hardfork::Istanbul {
+            precompiles.register(precompile_address(9), Rc::new(blake2f::blake2f));
+        }
+        if hardfork >= Hardfork::Cancun {
+            precompiles.register(precompile_address(10), Rc::new(kzg::point_evaluation));
+        }
+        precompiles
+    }
+
+    /// 从文件加载可信设置，替换0x0a点求值预编译合约使用的默认设置
+    pub fn load_trusted_setup(&mut self, path: &Path) -> Result<(), c_kzg::Error> {
+        let settings = kzg::load_trusted_setup(path)?;
+        self.register(
+            precompile_address(10),
+            kzg::point_evaluation_with(Rc::new(settings)),
+        );
+        Ok(())
+    }
+
+    /// 注册预编译合约，地址已存在时覆盖
+    pub fn register(&mut self, address: Address, precompile: Precompile) {
+        self.inner.insert(address, precompile);
+    }
+
+    pub fn get(&self, address: &Address) -> Option<Precompile> {
+        self.inner.get(address).cloned()
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.inner.contains_key(address)
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.inner.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use num_bigint::BigUint;
+    use once_cell::sync::Lazy;
+
+    /// 将0xff写入内存0x1f后以该字节为输入调用address，返回值复制到内存0x20处
+    fn call_code(op: &str, address: u8) -> Vec<u8> {
+        let value = if op == "f1" || op == "f2" { "6000" } else { "" };
+        hex::decode(format!(
+            "60ff600052602060206001601f{}60{:02x}5a{}",
+            value, address, op
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_call_precompile() {
+        Lazy::force(&INIT_LOG);
+        for op in ["f1", "f2", "f4", "fa"] {
+            let mut evm_test = Evm::new(call_code(op, 2));
+            evm_test.run();
+            assert!(evm_test.success);
+            assert_eq!(
+                BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+                BigUint::from(1u8)
+            );
+            // sha256(0xff)
+            assert_eq!(
+                hex::encode(&evm_test.memory[0x20..0x40]),
+                "a8100ae6aa1940d0b663bb31cd466142ebbdbd5187131b92d93818987832eb89"
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_precompile() {
+        Lazy::force(&INIT_LOG);
+        let mut evm_test = Evm::init_evm(call_code("fa", 0x20), Transaction::mock());
+        // 返回输入长度，固定消耗1000 gas
+        evm_test.register_precompile(
+            precompile_address(0x20),
+            Rc::new(|input: &[u8], gas_limit: u64| {
+                let gas_used = charge(1000, gas_limit)?;
+                Ok(PrecompileOutput::new(gas_used, vec![input.len() as u8]))
+            }),
+        );
+        evm_test.run();
+        assert_eq!(evm_test.memory[0x20], 1);
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(1u8)
+        );
+    }
+
+    #[test]
+    fn test_precompile_failure() {
+        Lazy::force(&INIT_LOG);
+        let mut evm_test = Evm::init_evm(call_code("fa", 0x20), Transaction::mock());
+        evm_test.register_precompile(
+            precompile_address(0x20),
+            Rc::new(|_: &[u8], _: u64| Err(PrecompileError::InvalidInput("always".into()))),
+        );
+        evm_test.run();
+        // 调用失败，子调用的gas全部消耗
+        assert!(evm_test.success);
+        assert_eq!(
+            BigUint::from_bytes_be(&evm_test.stack.get(1).data),
+            BigUint::from(0u8)
+        );
+        assert!(evm_test.gas_used > BigUint::from(9000u32));
+    }
+
+    #[test]
+    fn test_hardfork_pricing() {
+        // MODEXP 2 ** 2 % 5
+        let mut input = vec![0u8; 96];
+        for i in [31, 63, 95] {
+            input[i] = 1;
+        }
+        input.extend_from_slice(&[2, 2, 5]);
+        let modexp = |hardfork| {
+            Precompiles::hardfork(hardfork)
+                .get(&precompile_address(5))
+                .unwrap()(&input, u64::MAX)
+            .unwrap()
+        };
+        assert_eq!(modexp(Hardfork::Istanbul).gas_used, 0);
+        assert_eq!(modexp(Hardfork::Berlin).gas_used, 200);
+        assert_eq!(modexp(Hardfork::Cancun).output, vec![4]);
+
+        let ec_add = |hardfork| {
+            Precompiles::hardfork(hardfork)
+                .get(&precompile_address(6))
+                .unwrap()(&[], u64::MAX)
+            .unwrap()
+            .gas_used
+        };
+        assert_eq!(ec_add(Hardfork::Byzantium), 500);
+        assert_eq!(ec_add(Hardfork::Istanbul), 150);
+
+        // BLAKE2f从Istanbul开始可用
+        assert!(!Precompiles::hardfork(Hardfork::Byzantium).contains(&precompile_address(9)));
+        assert!(Precompiles::hardfork(Hardfork::Istanbul).contains(&precompile_address(9)));
+        // 点求值从Cancun开始可用
+        assert!(!Precompiles::hardfork(Hardfork::Berlin).contains(&precompile_address(10)));
+        assert!(Precompiles::hardfork(Hardfork::Cancun).contains(&precompile_address(10)));
+
+        let mut precompiles = Precompiles::hardfork(Hardfork::Berlin);
+        assert!(precompiles
+            .load_trusted_setup(Path::new("fixtures/missing_setup.txt"))
+            .is_err());
+        precompiles
+            .load_trusted_setup(Path::new("fixtures/trusted_setup.txt"))
+            .unwrap();
+        assert!(precompiles.contains(&precompile_address(10)));
+    }
+}
@@ -0,0 +1,183 @@
+/// 0x05 MODEXP
+/// 输入为 base_len(32) || exp_len(32) || mod_len(32) || base || exp || mod，不足部分右侧补0，
+/// 返回 base ** exp % mod，左侧补0到mod_len字节。
+/// gas在Berlin之前按EIP-198计算，之后按EIP-2565计算。
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+
+use super::{PrecompileError, PrecompileOutput, PrecompileResult};
+
+/// 从offset开始读取len字节，超出输入的部分补0
+fn read_padded(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut output = vec![0u8; len];
+    if offset < input.len() {
+        let end = input.len().min(offset.saturating_add(len));
+        output[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    output
+}
+
+/// 指数的有效位数，EIP-198中的adjusted_exponent_length
+fn adjusted_exp_len(exp_len: &BigUint, exp_head: &BigUint) -> BigUint {
+    let head_bits = if exp_head.is_zero() {
+        BigUint::zero()
+    } else {
+        BigUint::from(exp_head.bits() - 1)
+    };
+    if exp_len <= &BigUint::from(32u8) {
+        head_bits
+    } else {
+        (exp_len - 32u8) * 8u8 + head_bits
+    }
+}
+
+/// EIP-198的乘法复杂度
+fn mult_complexity_eip198(x: &BigUint) -> BigUint {
+    if x <= &BigUint::from(64u8) {
+        x * x
+    } else if x <= &BigUint::from(1024u32) {
+        x * x / 4u8 + x * 96u8 - 3072u32
+    } else {
+        x * x / 16u8 + x * 480u32 - 199680u32
+    }
+}
+
+/// EIP-2565的乘法复杂度，按8字节的字数计算
+fn mult_complexity_eip2565(x: &BigUint) -> BigUint {
+    let words = (x + 7u8) / 8u8;
+    &words * &words
+}
+
+fn gas_eip198(max_len: &BigUint, iterations: &BigUint) -> BigUint {
+    mult_complexity_eip198(max_len) * iterations.max(&BigUint::one()) / 20u8
+}
+
+fn gas_eip2565(max_len: &BigUint, iterations: &BigUint) -> BigUint {
+    let gas = mult_complexity_eip2565(max_len) * iterations.max(&BigUint::one()) / 3u8;
+    gas.max(BigUint::from(200u8))
+}
+
+fn run(input: &[u8], gas_limit: u64, gas: fn(&BigUint, &BigUint) -> BigUint) -> PrecompileResult {
+    let base_len = BigUint::from_bytes_be(&read_padded(input, 0, 32));
+    let exp_len = BigUint::from_bytes_be(&read_padded(input, 32, 32));
+    let mod_len = BigUint::from_bytes_be(&read_padded(input, 64, 32));
+    let max_len = (&base_len).max(&mod_len).clone();
+    let out_of_gas = |cost: &BigUint| cost > &BigUint::from(gas_limit);
+
+    // 先按最少一次迭代检查gas，保证之后读取的长度都在合理范围内
+    if out_of_gas(&gas(&max_len, &BigUint::one())) {
+        return Err(PrecompileError::OutOfGas);
+    }
+    let base_len = base_len.to_usize().ok_or(PrecompileError::OutOfGas)?;
+    let exp_head_offset = 96usize.saturating_add(base_len);
+    let exp_head_len = exp_len.to_usize().unwrap_or(usize::MAX).min(32);
+    let exp_head = BigUint::from_bytes_be(&read_padded(input, exp_head_offset, exp_head_len));
+    let cost = gas(&max_len, &adjusted_exp_len(&exp_len, &exp_head));
+    if out_of_gas(&cost) {
+        return Err(PrecompileError::OutOfGas);
+    }
+    let gas_used = cost.to_u64().unwrap();
+
+    let mod_len = mod_len.to_usize().ok_or(PrecompileError::OutOfGas)?;
+    if mod_len == 0 {
+        return Ok(PrecompileOutput::new(gas_used, Vec::new()));
+    }
+    let exp_len = exp_len.to_usize().ok_or(PrecompileError::OutOfGas)?;
+    let base = BigUint::from_bytes_be(&read_padded(input, 96, base_len));
+    let exp = BigUint::from_bytes_be(&read_padded(input, exp_head_offset, exp_len));
+    let modulus = BigUint::from_bytes_be(&read_padded(
+        input,
+        exp_head_offset.saturating_add(exp_len),
+        mod_len,
+    ));
+    let result = if modulus.is_zero() {
+        BigUint::zero()
+    } else {
+        base.modpow(&exp, &modulus)
+    };
+    let bytes = result.to_bytes_be();
+    let mut output = vec![0u8; mod_len];
+    if !result.is_zero() {
+        output[mod_len - bytes.len()..].copy_from_slice(&bytes);
+    }
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+/// Byzantium至Istanbul，EIP-198计价
+pub fn modexp_eip198(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run(input, gas_limit, gas_eip198)
+}
+
+/// Berlin及之后，EIP-2565计价
+pub fn modexp_eip2565(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run(input, gas_limit, gas_eip2565)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(base_len: u8, exp_len: u8, mod_len: u8, data: &str) -> Vec<u8> {
+        let mut input = Vec::new();
+        for len in [base_len, exp_len, mod_len] {
+            input.extend_from_slice(&[0u8; 31]);
+            input.push(len);
+        }
+        input.extend_from_slice(&hex::decode(data).unwrap());
+        input
+    }
+
+    #[test]
+    fn test_modexp() {
+        // EIP-198示例：费马小定理 3 ** (p - 1) % p = 1
+        let input = input(
+            1,
+            32,
+            32,
+            "03\
+             fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e\
+             fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+        );
+        let output = modexp_eip198(&input, u64::MAX).unwrap();
+        assert_eq!(
+            hex::encode(&output.output),
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        // 1024 * 255 / 20
+        assert_eq!(output.gas_used, 13056);
+        // 16 * 255 / 3
+        assert_eq!(modexp_eip2565(&input, u64::MAX).unwrap().gas_used, 1360);
+        assert_eq!(modexp_eip2565(&input, 1359), Err(PrecompileError::OutOfGas));
+    }
+
+    #[test]
+    fn test_modexp_edge_cases() {
+        // 模数为0时返回mod_len个0
+        let zero_mod = input(1, 1, 2, "0302");
+        let output = modexp_eip2565(&zero_mod, u64::MAX).unwrap();
+        assert_eq!(output.output, vec![0, 0]);
+        assert_eq!(output.gas_used, 200);
+
+        // mod_len为0时返回空输出，EIP-198下不消耗gas
+        let empty = input(0, 0, 0, "");
+        assert!(modexp_eip198(&empty, 0).unwrap().output.is_empty());
+        assert_eq!(modexp_eip2565(&empty, 200).unwrap().gas_used, 200);
+
+        // 输入被截断时右侧补0：模数读取为0x0500，2 ** 3 % 0x0500 = 8
+        let truncated = input(1, 1, 2, "020305");
+        let output = modexp_eip2565(&truncated, u64::MAX).unwrap();
+        assert_eq!(output.output, vec![0, 8]);
+
+        // 0 ** 0 = 1
+        let output = modexp_eip2565(&input(1, 1, 1, "000007"), u64::MAX).unwrap();
+        assert_eq!(output.output, vec![1]);
+
+        // 声明的长度过大时直接gas不足
+        let mut huge = input(0, 0, 0, "");
+        huge[..32].fill(0xff);
+        assert_eq!(
+            modexp_eip2565(&huge, u64::MAX),
+            Err(PrecompileError::OutOfGas)
+        );
+    }
+}
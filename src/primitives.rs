@@ -0,0 +1,260 @@
+/// 定长字节类型
+/// Address为20字节的账户地址，B256为32字节的哈希/存储字
+/// 与栈上的256位字之间可以相互转换
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+
+use crate::utils::keccak256;
+
+/// 十六进制字符串解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseBytesError {
+    // 字节长度不符
+    InvalidLength { expected: usize, actual: usize },
+    // 非法的十六进制字符
+    InvalidHex(hex::FromHexError),
+}
+
+impl fmt::Display for ParseBytesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBytesError::InvalidLength { expected, actual } => {
+                write!(
+                    f,
+                    "invalid length: expected {} bytes, got {}",
+                    expected, actual
+                )
+            }
+            ParseBytesError::InvalidHex(err) => write!(f, "invalid hex: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseBytesError {}
+
+/// 解析可带0x前缀的十六进制字符串
+fn parse_hex<const N: usize>(s: &str) -> Result<[u8; N], ParseBytesError> {
+    let s = s.strip_prefix("0x").or(s.strip_prefix("0X")).unwrap_or(s);
+    let bytes = hex::decode(s).map_err(ParseBytesError::InvalidHex)?;
+    bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| ParseBytesError::InvalidLength {
+            expected: N,
+            actual: bytes.len(),
+        })
+}
+
+/// 取256位字的低N字节，不足时高位补0
+fn word_to_bytes<const N: usize>(word: &BigUint) -> [u8; N] {
+    let bytes = word.to_bytes_be();
+    let mut out = [0u8; N];
+    if bytes.len() >= N {
+        out.copy_from_slice(&bytes[bytes.len() - N..]);
+    } else {
+        out[N - bytes.len()..].copy_from_slice(&bytes);
+    }
+    out
+}
+
+/// 20字节账户地址
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Address(pub [u8; 20]);
+
+impl Address {
+    pub const ZERO: Address = Address([0u8; 20]);
+
+    pub fn new(bytes: [u8; 20]) -> Self {
+        Self(bytes)
+    }
+
+    /// 从字节切片构造，长度必须为20
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self(bytes.try_into().expect("address must be 20 bytes"))
+    }
+
+    /// 取栈上256位字的低160位作为地址
+    pub fn from_word(word: &BigUint) -> Self {
+        Self(word_to_bytes(word))
+    }
+
+    /// 转换为栈上的256位字
+    pub fn to_word(&self) -> BigUint {
+        BigUint::from_bytes_be(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// EIP-55 校验和格式
+    /// 对小写十六进制地址做keccak256，哈希对应位置的半字节大于等于8时该字符大写
+    pub fn to_checksum(&self) -> String {
+        let lower = hex::encode(self.0);
+        let hash = keccak256(lower.as_bytes());
+        let checksum: String = lower
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+                if nibble >= 8 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect();
+        format!("0x{}", checksum)
+    }
+}
+
+impl FromStr for Address {
+    type Err = ParseBytesError;
+
+    /// 解析地址，不区分大小写，0x前缀可省略
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Self)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_checksum())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_checksum())
+    }
+}
+
+impl fmt::LowerHex for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl From<[u8; 20]> for Address {
+    fn from(bytes: [u8; 20]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// 32字节定长数据，用于哈希、存储键值等
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct B256(pub [u8; 32]);
+
+impl B256 {
+    pub const ZERO: B256 = B256([0u8; 32]);
+
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// 从字节切片构造，长度必须为32
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self(bytes.try_into().expect("B256 must be 32 bytes"))
+    }
+
+    /// 从栈上的256位字构造
+    pub fn from_word(word: &BigUint) -> Self {
+        Self(word_to_bytes(word))
+    }
+
+    /// 转换为栈上的256位字
+    pub fn to_word(&self) -> BigUint {
+        BigUint::from_bytes_be(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0u8; 32]
+    }
+}
+
+impl FromStr for B256 {
+    type Err = ParseBytesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Self)
+    }
+}
+
+impl fmt::Display for B256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for B256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl From<[u8; 32]> for B256 {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_checksum() {
+        // EIP-55 测试向量
+        for addr in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let address: Address = addr.to_lowercase().parse().unwrap();
+            assert_eq!(address.to_string(), addr);
+        }
+    }
+
+    #[test]
+    fn test_address_parse() {
+        let a: Address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse()
+            .unwrap();
+        let b: Address = "d8da6bf26964af9d7eed9e03e53415d37aa96045".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            "0x1234".parse::<Address>(),
+            Err(ParseBytesError::InvalidLength {
+                expected: 20,
+                actual: 2
+            })
+        );
+        assert!("0xzz".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn test_word_conversion() {
+        let address: Address = "0x0000000000000000000000000000000000000c42"
+            .parse()
+            .unwrap();
+        assert_eq!(address.to_word(), BigUint::from(0xc42u32));
+        assert_eq!(Address::from_word(&BigUint::from(0xc42u32)), address);
+        // 高于160位的部分被截断
+        let word = (BigUint::from(1u8) << 200u32) + BigUint::from(0xc42u32);
+        assert_eq!(Address::from_word(&word), address);
+
+        let hash = B256::from_word(&BigUint::from(1u8));
+        assert_eq!(
+            hash.to_string(),
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(hash.to_word(), BigUint::from(1u8));
+    }
+}
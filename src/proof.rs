@@ -0,0 +1,215 @@
+/// 账户与存储的Merkle证明
+/// 结构与eth_getProof的返回值一致：accountProof为状态树中账户的证明，
+/// storageProof为账户存储树中每个槽位的证明。
+/// 序列化为JSON时字段名和编码（0x开头的十六进制数值和字节）同样与eth_getProof一致。
+use num_bigint::BigUint;
+use serde::{Serialize, Serializer};
+
+use crate::db::KECCAK_EMPTY;
+use crate::primitives::{Address, B256};
+use crate::rlp;
+use crate::trie::{verify_proof, ProofError, TrieAccount, EMPTY_ROOT};
+use crate::utils::keccak256;
+
+/// 单个存储槽的证明
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageProof {
+    #[serde(serialize_with = "serialize_slot")]
+    pub key: BigUint,
+    #[serde(serialize_with = "serialize_quantity")]
+    pub value: BigUint,
+    #[serde(serialize_with = "serialize_nodes")]
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// 账户证明
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    #[serde(serialize_with = "serialize_address")]
+    pub address: Address,
+    #[serde(serialize_with = "serialize_quantity")]
+    pub balance: BigUint,
+    #[serde(serialize_with = "serialize_hash")]
+    pub code_hash: B256,
+    #[serde(serialize_with = "serialize_quantity")]
+    pub nonce: BigUint,
+    #[serde(serialize_with = "serialize_hash")]
+    pub storage_hash: B256,
+    #[serde(serialize_with = "serialize_nodes")]
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// 数值编码为不含前导0的十六进制，0编码为0x0
+fn serialize_quantity<S: Serializer>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:#x}", value))
+}
+
+/// 槽位编码为32字节十六进制
+fn serialize_slot<S: Serializer>(slot: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&B256::from_word(slot).to_string())
+}
+
+fn serialize_address<S: Serializer>(address: &Address, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:x}", address))
+}
+
+fn serialize_hash<S: Serializer>(hash: &B256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hash.to_string())
+}
+
+/// 证明中的每个节点编码为0x开头的十六进制字节
+fn serialize_nodes<S: Serializer>(nodes: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(nodes.iter().map(|node| format!("0x{}", hex::encode(node))))
+}
+
+/// 存储树中槽位的键
+pub fn storage_key(slot: &BigUint) -> [u8; 32] {
+    keccak256(B256::from_word(slot).as_bytes())
+}
+
+impl StorageProof {
+    /// 以存储树根验证槽位的值，不存在的槽位值为0
+    pub fn verify(&self, storage_hash: &B256) -> Result<(), ProofError> {
+        let value = match verify_proof(storage_hash, &storage_key(&self.key), &self.proof)? {
+            Some(encoded) => rlp::decode::<BigUint>(&encoded)?,
+            None => BigUint::from(0u8),
+        };
+        if value != self.value {
+            return Err(ProofError::ValueMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl AccountProof {
+    /// 以状态根验证账户信息及所有存储证明
+    /// 账户不存在时各字段应为空账户的值
+    pub fn verify(&self, state_root: &B256) -> Result<(), ProofError> {
+        let key = keccak256(self.address.as_bytes());
+        let account = match verify_proof(state_root, &key, &self.account_proof)? {
+            Some(encoded) => rlp::decode::<TrieAccount>(&encoded)?,
+            None => TrieAccount {
+                nonce: BigUint::from(0u8),
+                balance: BigUint::from(0u8),
+                storage_root: EMPTY_ROOT,
+                code_hash: KECCAK_EMPTY,
+            },
+        };
+        let claimed = TrieAccount {
+            nonce: self.nonce.clone(),
+            balance: self.balance.clone(),
+            storage_root: self.storage_hash,
+            code_hash: self.code_hash,
+        };
+        if account != claimed {
+            return Err(ProofError::ValueMismatch);
+        }
+        for storage_proof in &self.storage_proof {
+            storage_proof.verify(&self.storage_hash)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_db::{Account, AccountDb};
+    use std::collections::HashMap;
+
+    fn mock_db() -> AccountDb {
+        let mut db = AccountDb::mock();
+        let storage: HashMap<BigUint, BigUint> = (1u32..20)
+            .map(|i| (BigUint::from(i), BigUint::from(i * 1000)))
+            .collect();
+        db.insert(
+            "0x100000000000000000000000000000000000c0de"
+                .parse()
+                .unwrap(),
+            Account::new(
+                BigUint::from(5u8),
+                BigUint::from(1u8),
+                storage,
+                hex::decode("600054600101600055").unwrap(),
+            ),
+        );
+        db
+    }
+
+    #[test]
+    fn test_account_proof() {
+        let db = mock_db();
+        let root = db.state_root();
+        let address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let slots = [BigUint::from(3u8), BigUint::from(100u8)];
+        let proof = db.get_proof(&address, &slots);
+        assert_eq!(proof.balance, BigUint::from(5u8));
+        assert_eq!(proof.storage_hash, db.storage_root(&address));
+        assert_eq!(proof.storage_proof[0].value, BigUint::from(3000u32));
+        // 未写入的槽证明其值为0
+        assert_eq!(proof.storage_proof[1].value, BigUint::from(0u8));
+        assert_eq!(proof.verify(&root), Ok(()));
+
+        // 篡改余额或存储值后验证失败
+        let mut forged = proof.clone();
+        forged.balance = BigUint::from(6u8);
+        assert_eq!(forged.verify(&root), Err(ProofError::ValueMismatch));
+        let mut forged = proof.clone();
+        forged.storage_proof[0].value = BigUint::from(1u8);
+        assert_eq!(forged.verify(&root), Err(ProofError::ValueMismatch));
+        // 换一个状态根后证明中缺少对应节点
+        assert!(proof.verify(&AccountDb::mock().state_root()).is_err());
+    }
+
+    #[test]
+    fn test_absent_account_proof() {
+        let db = mock_db();
+        let address = "0x2000000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let proof = db.get_proof(&address, &[BigUint::from(1u8)]);
+        assert_eq!(proof.storage_hash, EMPTY_ROOT);
+        assert_eq!(proof.code_hash, KECCAK_EMPTY);
+        assert!(!proof.account_proof.is_empty());
+        assert_eq!(proof.verify(&db.state_root()), Ok(()));
+    }
+
+    #[test]
+    fn test_proof_json() {
+        let db = mock_db();
+        let address = "0x100000000000000000000000000000000000c0de"
+            .parse()
+            .unwrap();
+        let proof = db.get_proof(&address, &[BigUint::from(3u8)]);
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(
+            json["address"],
+            "0x100000000000000000000000000000000000c0de"
+        );
+        assert_eq!(json["balance"], "0x5");
+        assert_eq!(json["nonce"], "0x1");
+        assert_eq!(json["codeHash"], proof.code_hash.to_string());
+        assert_eq!(json["storageHash"], proof.storage_hash.to_string());
+        let account_proof = json["accountProof"].as_array().unwrap();
+        assert_eq!(account_proof.len(), proof.account_proof.len());
+        assert_eq!(
+            account_proof[0],
+            format!("0x{}", hex::encode(&proof.account_proof[0]))
+        );
+        let storage_proof = &json["storageProof"][0];
+        assert_eq!(
+            storage_proof["key"],
+            "0x0000000000000000000000000000000000000000000000000000000000000003"
+        );
+        assert_eq!(storage_proof["value"], "0xbb8");
+        assert!(storage_proof["proof"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("0x"));
+    }
+}
@@ -0,0 +1,638 @@
+/// RLP编码与解码
+/// 字节串和列表两种基本类型，整数按去掉前导0的大端字节编码。
+/// 合约地址推导、交易哈希和状态树节点都依赖RLP编码。
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use crate::primitives::{Address, B256};
+
+/// 单字节编码的上界，小于该值的单个字节编码为自身
+const SINGLE_BYTE_LIMIT: u8 = 0x80;
+/// 字节串前缀
+const STRING_OFFSET: u8 = 0x80;
+/// 列表前缀
+const LIST_OFFSET: u8 = 0xc0;
+/// 短字节串/短列表的最大长度
+const SHORT_LIMIT: usize = 55;
+/// 解码时列表的最大嵌套深度，防止不可信输入耗尽栈空间
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RlpError {
+    // 数据长度不足
+    UnexpectedEof,
+    // 解码完成后仍有剩余字节
+    TrailingBytes,
+    // 非规范编码，例如单字节被编码为长度1的字节串、长度带前导0
+    NonCanonical,
+    // 整数带前导0或超出类型范围
+    InvalidInteger,
+    ExpectedBytes,
+    ExpectedList,
+    // 字节串长度与类型不符
+    InvalidLength { expected: usize, actual: usize },
+    // 列表元素个数不符
+    ListLength { expected: usize, actual: usize },
+    // 列表嵌套超过MAX_DEPTH
+    TooDeep,
+}
+
+impl fmt::Display for RlpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RlpError::UnexpectedEof => write!(f, "unexpected end of input"),
+            RlpError::TrailingBytes => write!(f, "trailing bytes after rlp item"),
+            RlpError::NonCanonical => write!(f, "non-canonical rlp encoding"),
+            RlpError::InvalidInteger => write!(f, "invalid rlp integer"),
+            RlpError::ExpectedBytes => write!(f, "expected rlp bytes, got list"),
+            RlpError::ExpectedList => write!(f, "expected rlp list, got bytes"),
+            RlpError::InvalidLength { expected, actual } => {
+                write!(
+                    f,
+                    "invalid length: expected {} bytes, got {}",
+                    expected, actual
+                )
+            }
+            RlpError::ListLength { expected, actual } => {
+                write!(
+                    f,
+                    "invalid list length: expected {} items, got {}",
+                    expected, actual
+                )
+            }
+            RlpError::TooDeep => write!(f, "rlp list nested deeper than {}", MAX_DEPTH),
+        }
+    }
+}
+
+impl std::error::Error for RlpError {}
+
+/// 解码后的RLP数据
+#[derive(Debug, Clone, PartialEq)]
+pub enum RlpItem {
+    Bytes(Vec<u8>),
+    List(Vec<RlpItem>),
+}
+
+impl RlpItem {
+    /// 解码完整的一项，不允许有剩余字节
+    pub fn decode(bytes: &[u8]) -> Result<Self, RlpError> {
+        let (item, consumed) = Self::decode_prefix(bytes)?;
+        if consumed != bytes.len() {
+            return Err(RlpError::TrailingBytes);
+        }
+        Ok(item)
+    }
+
+    /// 解码开头的一项，返回该项及其占用的字节数
+    pub fn decode_prefix(bytes: &[u8]) -> Result<(Self, usize), RlpError> {
+        Self::decode_nested(bytes, 0)
+    }
+
+    fn decode_nested(bytes: &[u8], depth: usize) -> Result<(Self, usize), RlpError> {
+        let (is_list, header_len, payload_len) = decode_header(bytes)?;
+        let end = header_len + payload_len;
+        let payload = &bytes[header_len..end];
+        if !is_list {
+            return Ok((RlpItem::Bytes(payload.to_vec()), end));
+        }
+        if depth >= MAX_DEPTH {
+            return Err(RlpError::TooDeep);
+        }
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while offset < payload.len() {
+            let (item, consumed) = Self::decode_nested(&payload[offset..], depth + 1)?;
+            items.push(item);
+            offset += consumed;
+        }
+        Ok((RlpItem::List(items), end))
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], RlpError> {
+        match self {
+            RlpItem::Bytes(bytes) => Ok(bytes),
+            RlpItem::List(_) => Err(RlpError::ExpectedBytes),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[RlpItem], RlpError> {
+        match self {
+            RlpItem::List(items) => Ok(items),
+            RlpItem::Bytes(_) => Err(RlpError::ExpectedList),
+        }
+    }
+
+    /// 按元素个数检查列表
+    pub fn as_list_of(&self, expected: usize) -> Result<&[RlpItem], RlpError> {
+        let items = self.as_list()?;
+        if items.len() != expected {
+            return Err(RlpError::ListLength {
+                expected,
+                actual: items.len(),
+            });
+        }
+        Ok(items)
+    }
+}
+
+/// 解析前缀，返回(是否为列表, 前缀长度, 数据长度)
+fn decode_header(bytes: &[u8]) -> Result<(bool, usize, usize), RlpError> {
+    let prefix = *bytes.first().ok_or(RlpError::UnexpectedEof)?;
+    let (is_list, header_len, payload_len) = match prefix {
+        0x00..=0x7f => return Ok((false, 0, 1)),
+        0x80..=0xb7 => {
+            let len = (prefix - STRING_OFFSET) as usize;
+            // 小于0x80的单个字节必须直接编码
+            if len == 1 && bytes.get(1).is_some_and(|b| *b < SINGLE_BYTE_LIMIT) {
+                return Err(RlpError::NonCanonical);
+            }
+            (false, 1, len)
+        }
+        0xb8..=0xbf => {
+            let len_of_len = (prefix - 0xb7) as usize;
+            (
+                false,
+                1 + len_of_len,
+                decode_long_length(bytes, len_of_len)?,
+            )
+        }
+        0xc0..=0xf7 => (true, 1, (prefix - LIST_OFFSET) as usize),
+        0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            (true, 1 + len_of_len, decode_long_length(bytes, len_of_len)?)
+        }
+    };
+    // 长度字段可能接近usize上限，相加时需检查溢出
+    match header_len.checked_add(payload_len) {
+        Some(end) if end <= bytes.len() => {}
+        _ => return Err(RlpError::UnexpectedEof),
+    }
+    Ok((is_list, header_len, payload_len))
+}
+
+/// 解析长字节串/长列表的长度字段
+fn decode_long_length(bytes: &[u8], len_of_len: usize) -> Result<usize, RlpError> {
+    let len_bytes = bytes
+        .get(1..1 + len_of_len)
+        .ok_or(RlpError::UnexpectedEof)?;
+    if len_bytes[0] == 0 || len_of_len > std::mem::size_of::<usize>() {
+        return Err(RlpError::NonCanonical);
+    }
+    let len = len_bytes
+        .iter()
+        .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+    if len <= SHORT_LIMIT {
+        return Err(RlpError::NonCanonical);
+    }
+    Ok(len)
+}
+
+/// 写入前缀
+fn encode_header(len: usize, offset: u8, out: &mut Vec<u8>) {
+    if len <= SHORT_LIMIT {
+        out.push(offset + len as u8);
+    } else {
+        let len_be = len.to_be_bytes();
+        let len_bytes = to_minimal_be(&len_be);
+        out.push(offset + SHORT_LIMIT as u8 + len_bytes.len() as u8);
+        out.extend_from_slice(len_bytes);
+    }
+}
+
+/// 去掉大端字节的前导0
+fn to_minimal_be(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// 编码字节串
+pub fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    if bytes.len() == 1 && bytes[0] < SINGLE_BYTE_LIMIT {
+        out.push(bytes[0]);
+    } else {
+        encode_header(bytes.len(), STRING_OFFSET, out);
+        out.extend_from_slice(bytes);
+    }
+}
+
+/// 将已编码的列表元素拼接结果包装为列表
+pub fn encode_list_payload(payload: &[u8], out: &mut Vec<u8>) {
+    encode_header(payload.len(), LIST_OFFSET, out);
+    out.extend_from_slice(payload);
+}
+
+/// RLP编码
+pub trait Encodable {
+    /// 将编码结果追加到out
+    fn rlp_append(&self, out: &mut Vec<u8>);
+
+    fn rlp_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.rlp_append(&mut out);
+        out
+    }
+}
+
+/// RLP解码
+pub trait Decodable: Sized {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError>;
+}
+
+/// 编码任意可编码的值
+/// ```
+/// use mini_evm::rlp::encode;
+/// assert_eq!(encode("dog"), vec![0x83, b'd', b'o', b'g']);
+/// assert_eq!(encode(&vec![1u64, 2u64]), vec![0xc2, 0x01, 0x02]);
+/// ```
+pub fn encode<T: Encodable + ?Sized>(value: &T) -> Vec<u8> {
+    value.rlp_bytes()
+}
+
+/// 解码完整的RLP数据
+/// ```
+/// use mini_evm::rlp::decode;
+/// let value: u64 = decode(&[0x82, 0x04, 0x00]).unwrap();
+/// assert_eq!(value, 1024);
+/// ```
+pub fn decode<T: Decodable>(bytes: &[u8]) -> Result<T, RlpError> {
+    T::rlp_decode(&RlpItem::decode(bytes)?)
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        (**self).rlp_append(out);
+    }
+}
+
+impl Encodable for RlpItem {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        match self {
+            RlpItem::Bytes(bytes) => encode_bytes(bytes, out),
+            RlpItem::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.rlp_append(&mut payload);
+                }
+                encode_list_payload(&payload, out);
+            }
+        }
+    }
+}
+
+impl Decodable for RlpItem {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        Ok(item.clone())
+    }
+}
+
+impl Encodable for [u8] {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self, out);
+    }
+}
+
+impl Encodable for Vec<u8> {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self, out);
+    }
+}
+
+impl Decodable for Vec<u8> {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        Ok(item.as_bytes()?.to_vec())
+    }
+}
+
+impl Encodable for str {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+}
+
+impl Encodable for String {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+}
+
+/// 除Vec<u8>外的Vec编码为列表
+impl<T: Encodable> Encodable for Vec<T> {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        for item in self {
+            item.rlp_append(&mut payload);
+        }
+        encode_list_payload(&payload, out);
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        item.as_list()?.iter().map(T::rlp_decode).collect()
+    }
+}
+
+/// None编码为空字节串，用于合约创建交易中的to字段
+impl<T: Encodable> Encodable for Option<T> {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => value.rlp_append(out),
+            None => encode_bytes(&[], out),
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for Option<T> {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        match item {
+            RlpItem::Bytes(bytes) if bytes.is_empty() => Ok(None),
+            _ => T::rlp_decode(item).map(Some),
+        }
+    }
+}
+
+impl<A: Encodable, B: Encodable> Encodable for (A, B) {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        self.0.rlp_append(&mut payload);
+        self.1.rlp_append(&mut payload);
+        encode_list_payload(&payload, out);
+    }
+}
+
+impl<A: Decodable, B: Decodable> Decodable for (A, B) {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        let items = item.as_list_of(2)?;
+        Ok((A::rlp_decode(&items[0])?, B::rlp_decode(&items[1])?))
+    }
+}
+
+/// 解码整数的字节，不允许前导0
+fn integer_bytes(item: &RlpItem) -> Result<&[u8], RlpError> {
+    let bytes = item.as_bytes()?;
+    if bytes.first() == Some(&0) {
+        return Err(RlpError::InvalidInteger);
+    }
+    Ok(bytes)
+}
+
+macro_rules! impl_rlp_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl Encodable for $ty {
+                fn rlp_append(&self, out: &mut Vec<u8>) {
+                    encode_bytes(to_minimal_be(&self.to_be_bytes()), out);
+                }
+            }
+
+            impl Decodable for $ty {
+                fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+                    let bytes = integer_bytes(item)?;
+                    if bytes.len() > std::mem::size_of::<$ty>() {
+                        return Err(RlpError::InvalidInteger);
+                    }
+                    Ok(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as $ty))
+                }
+            }
+        )*
+    };
+}
+
+impl_rlp_uint!(u16, u32, u64, u128, usize);
+
+impl Encodable for bool {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        (*self as u64).rlp_append(out);
+    }
+}
+
+impl Decodable for bool {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        match u64::rlp_decode(item)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(RlpError::InvalidInteger),
+        }
+    }
+}
+
+impl Encodable for BigUint {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(to_minimal_be(&self.to_bytes_be()), out);
+    }
+}
+
+impl Decodable for BigUint {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        Ok(BigUint::from_bytes_be(integer_bytes(item)?))
+    }
+}
+
+/// 定长字节类型的解码
+fn fixed_bytes<const N: usize>(item: &RlpItem) -> Result<[u8; N], RlpError> {
+    let bytes = item.as_bytes()?;
+    bytes.try_into().map_err(|_| RlpError::InvalidLength {
+        expected: N,
+        actual: bytes.len(),
+    })
+}
+
+impl Encodable for Address {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+}
+
+impl Decodable for Address {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        fixed_bytes(item).map(Address)
+    }
+}
+
+impl Encodable for B256 {
+    fn rlp_append(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+}
+
+impl Decodable for B256 {
+    fn rlp_decode(item: &RlpItem) -> Result<Self, RlpError> {
+        fixed_bytes(item).map(B256)
+    }
+}
+
+/// 为结构体实现RLP编解码，按字段声明顺序编码为列表
+/// ```
+/// use mini_evm::impl_rlp;
+/// use mini_evm::rlp::{decode, encode};
+/// #[derive(Debug, PartialEq)]
+/// struct Point {
+///     x: u64,
+///     y: u64,
+/// }
+/// impl_rlp!(Point { x, y });
+/// let point = Point { x: 1, y: 2 };
+/// assert_eq!(encode(&point), vec![0xc2, 0x01, 0x02]);
+/// assert_eq!(decode::<Point>(&encode(&point)).unwrap(), point);
+/// ```
+#[macro_export]
+macro_rules! impl_rlp {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::rlp::Encodable for $name {
+            fn rlp_append(&self, out: &mut Vec<u8>) {
+                let mut payload = Vec::new();
+                $( $crate::rlp::Encodable::rlp_append(&self.$field, &mut payload); )*
+                $crate::rlp::encode_list_payload(&payload, out);
+            }
+        }
+
+        impl $crate::rlp::Decodable for $name {
+            fn rlp_decode(
+                item: &$crate::rlp::RlpItem,
+            ) -> Result<Self, $crate::rlp::RlpError> {
+                let expected = [$(stringify!($field)),*].len();
+                let mut items = item.as_list_of(expected)?.iter();
+                Ok(Self {
+                    $( $field: $crate::rlp::Decodable::rlp_decode(items.next().unwrap())?, )*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_bytes() {
+        // 以太坊wiki中的测试向量
+        assert_eq!(encode(""), vec![0x80]);
+        assert_eq!(encode(&vec![0x0fu8]), vec![0x0f]);
+        assert_eq!(encode(&vec![0x80u8]), vec![0x81, 0x80]);
+        assert_eq!(encode(&vec![0x04u8, 0x00]), vec![0x82, 0x04, 0x00]);
+        let lorem = "Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut expected = vec![0xb8, 0x38];
+        expected.extend_from_slice(lorem.as_bytes());
+        assert_eq!(encode(lorem), expected);
+    }
+
+    #[test]
+    fn test_encode_integer() {
+        assert_eq!(encode(&0u64), vec![0x80]);
+        assert_eq!(encode(&15u64), vec![0x0f]);
+        assert_eq!(encode(&1024u64), vec![0x82, 0x04, 0x00]);
+        assert_eq!(encode(&BigUint::from(0u8)), vec![0x80]);
+        assert_eq!(encode(&BigUint::from(1024u32)), vec![0x82, 0x04, 0x00]);
+    }
+
+    #[test]
+    fn test_encode_list() {
+        assert_eq!(encode(&Vec::<u64>::new()), vec![0xc0]);
+        assert_eq!(
+            encode(&vec!["cat".to_string(), "dog".to_string()]),
+            hex::decode("c88363617483646f67").unwrap()
+        );
+        // 集合论表示 [ [], [[]], [ [], [[]] ] ]
+        let set = RlpItem::List(vec![
+            RlpItem::List(vec![]),
+            RlpItem::List(vec![RlpItem::List(vec![])]),
+            RlpItem::List(vec![
+                RlpItem::List(vec![]),
+                RlpItem::List(vec![RlpItem::List(vec![])]),
+            ]),
+        ]);
+        let encoded = encode(&set);
+        assert_eq!(encoded, hex::decode("c7c0c1c0c3c0c1c0").unwrap());
+        assert_eq!(RlpItem::decode(&encoded).unwrap(), set);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode::<u64>(&[0x82, 0x04, 0x00]).unwrap(), 1024);
+        assert_eq!(decode::<Vec<u8>>(&[0x80]).unwrap(), Vec::<u8>::new());
+        let address: Address = "0x1000000000000000000000000000000000000c42"
+            .parse()
+            .unwrap();
+        assert_eq!(decode::<Address>(&encode(&address)).unwrap(), address);
+        let pair: (Address, Vec<B256>) = (address, vec![B256::ZERO]);
+        assert_eq!(
+            decode::<(Address, Vec<B256>)>(&encode(&pair)).unwrap(),
+            pair
+        );
+        let long = vec![0xaau8; 1024];
+        assert_eq!(decode::<Vec<u8>>(&encode(&long)).unwrap(), long);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(RlpItem::decode(&[]), Err(RlpError::UnexpectedEof));
+        assert_eq!(RlpItem::decode(&[0x83, 0x01]), Err(RlpError::UnexpectedEof));
+        assert_eq!(RlpItem::decode(&[0x01, 0x02]), Err(RlpError::TrailingBytes));
+        // 单字节必须直接编码
+        assert_eq!(RlpItem::decode(&[0x81, 0x01]), Err(RlpError::NonCanonical));
+        // 短字节串不能使用长格式
+        assert_eq!(
+            RlpItem::decode(&[0xb8, 0x01, 0x80]),
+            Err(RlpError::NonCanonical)
+        );
+        assert_eq!(
+            decode::<u64>(&[0x82, 0x00, 0x01]),
+            Err(RlpError::InvalidInteger)
+        );
+        assert_eq!(
+            decode::<u16>(&[0x83, 0x01, 0x00, 0x00]),
+            Err(RlpError::InvalidInteger)
+        );
+        assert_eq!(decode::<u64>(&[0xc0]), Err(RlpError::ExpectedBytes));
+        // 长度字段使header_len + payload_len溢出
+        assert_eq!(
+            RlpItem::decode(&[0xbf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(RlpError::UnexpectedEof)
+        );
+        assert_eq!(
+            RlpItem::decode(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(RlpError::UnexpectedEof)
+        );
+    }
+
+    /// depth层嵌套的空列表
+    fn nested_list(depth: usize) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for _ in 0..depth {
+            let mut out = Vec::new();
+            encode_list_payload(&encoded, &mut out);
+            encoded = out;
+        }
+        encoded
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert!(RlpItem::decode(&nested_list(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            RlpItem::decode(&nested_list(MAX_DEPTH + 1)),
+            Err(RlpError::TooDeep)
+        );
+        // 很深的嵌套返回错误而不是栈溢出
+        let mut headers = Vec::new();
+        let mut payload_len = 0usize;
+        for _ in 0..100_000 {
+            let header = if payload_len <= SHORT_LIMIT {
+                vec![LIST_OFFSET + payload_len as u8]
+            } else {
+                let len = payload_len.to_be_bytes();
+                let len = &len[len.iter().position(|&byte| byte != 0).unwrap()..];
+                let mut header = vec![LIST_OFFSET + SHORT_LIMIT as u8 + len.len() as u8];
+                header.extend_from_slice(len);
+                header
+            };
+            payload_len += header.len();
+            headers.push(header);
+        }
+        let deep: Vec<u8> = headers.into_iter().rev().flatten().collect();
+        assert_eq!(RlpItem::decode(&deep), Err(RlpError::TooDeep));
+    }
+}
@@ -0,0 +1,419 @@
+/// 世界状态的导出与导入
+/// JSON格式与geth的state dump一致，accounts中的账户格式与创世文件的alloc相同，
+/// 数据库中有区块哈希时额外写入blockHashes（区块号 -> 哈希，格式与env.json相同）；
+/// 二进制格式为RLP编码的
+/// [版本, 状态根, [[地址, nonce, 余额, 代码, [[槽位, 值], ...]], ...], [[区块号, 哈希], ...]]。
+/// 导入时都会重新计算状态根并与文件中记录的状态根比较，区块哈希不属于状态根。
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::fake_db::{Account, AccountDb};
+use crate::genesis::{parse_alloc, parse_b256, parse_quantity, GenesisAccountJson, GenesisError};
+use crate::impl_rlp;
+use crate::primitives::{Address, B256};
+use crate::rlp::{self, RlpError, RlpItem};
+use crate::utils::keccak256;
+
+/// 二进制格式的版本
+const BINARY_VERSION: u64 = 1;
+
+#[derive(Debug)]
+pub enum DumpError {
+    Io(std::io::Error),
+    Json(GenesisError),
+    Rlp(RlpError),
+    UnsupportedVersion(u64),
+    // 导入后的状态根与文件中记录的不一致
+    StateRootMismatch { expected: B256, actual: B256 },
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::Io(err) => write!(f, "io error: {}", err),
+            DumpError::Json(err) => write!(f, "{}", err),
+            DumpError::Rlp(err) => write!(f, "rlp error: {}", err),
+            DumpError::UnsupportedVersion(version) => {
+                write!(f, "unsupported dump version: {}", version)
+            }
+            DumpError::StateRootMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "state root mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl From<std::io::Error> for DumpError {
+    fn from(err: std::io::Error) -> Self {
+        DumpError::Io(err)
+    }
+}
+
+impl From<GenesisError> for DumpError {
+    fn from(err: GenesisError) -> Self {
+        DumpError::Json(err)
+    }
+}
+
+impl From<serde_json::Error> for DumpError {
+    fn from(err: serde_json::Error) -> Self {
+        DumpError::Json(GenesisError::Json(err))
+    }
+}
+
+impl From<RlpError> for DumpError {
+    fn from(err: RlpError) -> Self {
+        DumpError::Rlp(err)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpAccountJson {
+    balance: String,
+    nonce: String,
+    root: String,
+    code_hash: String,
+    code: String,
+    storage: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpJson {
+    root: String,
+    accounts: BTreeMap<String, DumpAccountJson>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    block_hashes: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoadJson {
+    root: Option<String>,
+    accounts: HashMap<String, GenesisAccountJson>,
+    #[serde(default)]
+    block_hashes: HashMap<String, String>,
+}
+
+/// 二进制格式中的账户
+struct DumpAccount {
+    address: Address,
+    nonce: BigUint,
+    balance: BigUint,
+    code: Vec<u8>,
+    storage: Vec<(BigUint, BigUint)>,
+}
+
+impl_rlp!(DumpAccount {
+    address,
+    nonce,
+    balance,
+    code,
+    storage
+});
+
+/// 二进制格式
+struct DumpBinary {
+    version: u64,
+    root: B256,
+    accounts: Vec<DumpAccount>,
+    block_hashes: Vec<(BigUint, B256)>,
+}
+
+impl_rlp!(DumpBinary {
+    version,
+    root,
+    accounts,
+    block_hashes
+});
+
+/// 检查导入的状态根
+fn check_root(db: &AccountDb, expected: Option<B256>) -> Result<(), DumpError> {
+    let actual = db.state_root();
+    match expected {
+        Some(expected) if expected != actual => {
+            Err(DumpError::StateRootMismatch { expected, actual })
+        }
+        _ => Ok(()),
+    }
+}
+
+impl AccountDb {
+    /// 导出为JSON，账户和存储按键排序
+    pub fn dump_json(&self) -> String {
+        let accounts = self
+            .accounts()
+            .map(|(address, account)| {
+                let storage = account
+                    .storage
+                    .iter()
+                    .map(|(slot, value)| {
+                        (
+                            B256::from_word(slot).to_string(),
+                            B256::from_word(value).to_string(),
+                        )
+                    })
+                    .collect();
+                let json = DumpAccountJson {
+                    balance: account.balance.to_string(),
+                    nonce: format!("{:#x}", account.nonce),
+                    root: self.storage_root(address).to_string(),
+                    code_hash: B256::from(keccak256(&account.code)).to_string(),
+                    code: format!("0x{}", hex::encode(&account.code)),
+                    storage,
+                };
+                (format!("{:x}", address), json)
+            })
+            .collect();
+        let block_hashes = self
+            .block_hashes()
+            .map(|(number, hash)| (number.to_string(), hash.to_string()))
+            .collect();
+        let dump = DumpJson {
+            root: self.state_root().to_string(),
+            accounts,
+            block_hashes,
+        };
+        serde_json::to_string_pretty(&dump).expect("state dump is always serializable")
+    }
+
+    /// 从JSON导入，文件中有root字段时校验状态根
+    /// ```
+    /// use mini_evm::fake_db::AccountDb;
+    /// let db = AccountDb::mock();
+    /// let restored = AccountDb::load_json(&db.dump_json()).unwrap();
+    /// assert_eq!(restored.state_root(), db.state_root());
+    /// ```
+    pub fn load_json(json: &str) -> Result<Self, DumpError> {
+        let dump: LoadJson = serde_json::from_str(json)?;
+        let mut db = AccountDb::new();
+        for (address, account) in parse_alloc(&dump.accounts)? {
+            db.insert(address, account);
+        }
+        for (number, hash) in &dump.block_hashes {
+            db.insert_block_hash(
+                parse_quantity(&Value::String(number.clone()))?,
+                parse_b256(hash)?,
+            );
+        }
+        let root = match dump.root {
+            Some(root) => Some(
+                root.parse()
+                    .map_err(|_| GenesisError::InvalidBytes(root.clone()))?,
+            ),
+            None => None,
+        };
+        check_root(&db, root)?;
+        Ok(db)
+    }
+
+    /// 导出为二进制
+    pub fn dump_binary(&self) -> Vec<u8> {
+        let mut accounts: Vec<DumpAccount> = self
+            .accounts()
+            .map(|(address, account)| {
+                let mut storage: Vec<(BigUint, BigUint)> = account
+                    .storage
+                    .iter()
+                    .map(|(slot, value)| (slot.clone(), value.clone()))
+                    .collect();
+                storage.sort();
+                DumpAccount {
+                    address: *address,
+                    nonce: account.nonce.clone(),
+                    balance: account.balance.clone(),
+                    code: account.code.clone(),
+                    storage,
+                }
+            })
+            .collect();
+        accounts.sort_by_key(|account| account.address);
+        let mut block_hashes: Vec<(BigUint, B256)> = self
+            .block_hashes()
+            .map(|(number, hash)| (number.clone(), *hash))
+            .collect();
+        block_hashes.sort();
+        rlp::encode(&DumpBinary {
+            version: BINARY_VERSION,
+            root: self.state_root(),
+            accounts,
+            block_hashes,
+        })
+    }
+
+    /// 从二进制导入并校验状态根
+    pub fn load_binary(bytes: &[u8]) -> Result<Self, DumpError> {
+        // 先检查版本，不同版本的账户格式可能不同
+        let item = RlpItem::decode(bytes)?;
+        let version: u64 =
+            rlp::Decodable::rlp_decode(item.as_list()?.first().ok_or(RlpError::UnexpectedEof)?)?;
+        if version != BINARY_VERSION {
+            return Err(DumpError::UnsupportedVersion(version));
+        }
+        let dump: DumpBinary = rlp::Decodable::rlp_decode(&item)?;
+        let mut db = AccountDb::new();
+        for (number, hash) in dump.block_hashes {
+            db.insert_block_hash(number, hash);
+        }
+        for account in dump.accounts {
+            db.insert(
+                account.address,
+                Account::new(
+                    account.balance,
+                    account.nonce,
+                    account.storage.into_iter().collect(),
+                    account.code,
+                ),
+            );
+        }
+        check_root(&db, Some(dump.root))?;
+        Ok(db)
+    }
+
+    /// 保存到文件，扩展名为.json时使用JSON格式，否则使用二进制格式
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), DumpError> {
+        let path = path.as_ref();
+        if is_json(path) {
+            std::fs::write(path, self.dump_json())?;
+        } else {
+            std::fs::write(path, self.dump_binary())?;
+        }
+        Ok(())
+    }
+
+    /// 从文件导入，格式与save相同
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DumpError> {
+        let path = path.as_ref();
+        if is_json(path) {
+            Self::load_json(&std::fs::read_to_string(path)?)
+        } else {
+            Self::load_binary(&std::fs::read(path)?)
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{shared, Database};
+    use crate::evm::*;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// 执行一笔写存储并转账的交易后的状态
+    fn executed_db() -> AccountDb {
+        Lazy::force(&INIT_LOG);
+        let db = Rc::new(RefCell::new(AccountDb::mock()));
+        // SSTORE slot1 = 0xf1，向0x1000..0c42转账1 wei
+        let bytes = hex::decode(
+            "60f16001556000600060006000600173100000000000000000000000000000000000\
+             0c425af1",
+        )
+        .unwrap();
+        let mut evm_test = Evm::init_evm_with_db(bytes, Transaction::mock(), db.clone());
+        evm_test.run();
+        assert!(evm_test.success);
+        drop(evm_test);
+        Rc::try_unwrap(db).unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let db = executed_db();
+        let json = db.dump_json();
+        let restored = AccountDb::load_json(&json).unwrap();
+        assert_eq!(restored.state_root(), db.state_root());
+        assert_eq!(restored.dump_json(), json);
+
+        // 导入的状态可以继续执行交易
+        let state = shared(restored);
+        let mut evm_test =
+            Evm::init_evm_with_db(hex::decode("600154").unwrap(), Transaction::mock(), state);
+        evm_test.run();
+        assert_eq!(
+            hex::encode(evm_test.stack.get(1).data),
+            "00000000000000000000000000000000000000000000000000000000000000f1"
+        );
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let db = executed_db();
+        let bytes = db.dump_binary();
+        assert!(bytes.len() < db.dump_json().len());
+        let restored = AccountDb::load_binary(&bytes).unwrap();
+        assert_eq!(restored.state_root(), db.state_root());
+        assert_eq!(restored.dump_binary(), bytes);
+    }
+
+    #[test]
+    fn test_block_hashes_roundtrip() {
+        let mut db = executed_db();
+        db.insert_block_hash(BigUint::from(1u8), B256::from([1u8; 32]));
+        db.insert_block_hash(BigUint::from(256u32), B256::from([2u8; 32]));
+        let json = db.dump_json();
+        assert!(json.contains("\"blockHashes\""));
+        let restored = [
+            AccountDb::load_json(&json).unwrap(),
+            AccountDb::load_binary(&db.dump_binary()).unwrap(),
+        ];
+        for mut restored in restored {
+            assert_eq!(restored.state_root(), db.state_root());
+            assert_eq!(
+                restored.block_hash(&BigUint::from(1u8)),
+                B256::from([1u8; 32])
+            );
+            assert_eq!(
+                restored.block_hash(&BigUint::from(256u32)),
+                B256::from([2u8; 32])
+            );
+        }
+    }
+
+    #[test]
+    fn test_root_mismatch() {
+        let db = executed_db();
+        let json = db
+            .dump_json()
+            .replacen("\"balance\": \"99\"", "\"balance\": \"98\"", 1);
+        assert!(matches!(
+            AccountDb::load_json(&json),
+            Err(DumpError::StateRootMismatch { .. })
+        ));
+        let mut bytes = AccountDb::mock().dump_binary();
+        *bytes.last_mut().unwrap() ^= 1;
+        assert!(AccountDb::load_binary(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let db = executed_db();
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["state.json", "state.bin"] {
+            let path = dir.path().join(name);
+            db.save(&path).unwrap();
+            assert_eq!(
+                AccountDb::load(&path).unwrap().state_root(),
+                db.state_root()
+            );
+        }
+    }
+}
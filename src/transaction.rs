@@ -3,20 +3,24 @@ use std::collections::HashMap;
 use num_bigint::BigUint;
 use num_traits::zero;
 
+use crate::primitives::{Address, B256};
+
 #[derive(Debug, Clone)]
 pub struct Transaction {
     nonce: BigUint,
     gas_price: BigUint,
     gas_limit: BigUint,
-    to: String,
+    to: Address,
     value: BigUint,
     data: String,
-    caller: String,
-    origin: String,
-    this_addr: String,
+    caller: Address,
+    origin: Address,
+    this_addr: Address,
     v: BigUint,
     r: BigUint,
     s: BigUint,
+    // blob交易（EIP-4844）携带的blob版本哈希，子调用沿用同一组哈希
+    blob_hashes: Vec<B256>,
 }
 impl Transaction {
     pub fn mock() -> Self {
@@ -31,24 +35,25 @@ impl Transaction {
             // caller: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
             // origin: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
             // to: "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
-            this_addr: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
-            caller: "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
-            origin: "0x9bbfed6889322e016e0a02ee459d306fc19545d8".to_string(),
-            to: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
+            this_addr: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
+            caller: "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
+            origin: "0x9bbfed6889322e016e0a02ee459d306fc19545d8".parse().unwrap(),
+            to: "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse().unwrap(),
             gas_limit: BigUint::from(10000u32),
             gas_price: BigUint::from(1u8),
+            blob_hashes: Vec::new(),
         }
     }
     pub fn init(
         nonce: BigUint,
         gas_price: BigUint,
         gas_limit: BigUint,
-        to: String,
+        to: Address,
         value: BigUint,
         data: String,
-        caller: String,
-        origin: String,
-        this_addr: String,
+        caller: Address,
+        origin: Address,
+        this_addr: Address,
         v: BigUint,
         r: BigUint,
         s: BigUint,
@@ -66,6 +71,7 @@ impl Transaction {
             to: to,
             gas_limit: gas_limit,
             gas_price: gas_price,
+            blob_hashes: Vec::new(),
         }
     }
     pub fn get_nonce(&self) -> &BigUint {
@@ -80,8 +86,8 @@ impl Transaction {
     pub fn get_s(&self) -> &BigUint {
         &self.s
     }
-    pub fn get_this_addr(&self) -> String {
-        self.this_addr.to_string()
+    pub fn get_this_addr(&self) -> Address {
+        self.this_addr
     }
     pub fn get_value(&self) -> &BigUint {
         &self.value
@@ -89,19 +95,36 @@ impl Transaction {
     pub fn get_data(&self) -> String {
         self.data.to_string()
     }
-    pub fn get_caller(&self) -> String {
-        self.caller.to_string()
+    /// 调用数据的字节，十六进制字符串可以带0x前缀
+    pub fn get_calldata(&self) -> Vec<u8> {
+        let data = self.data.strip_prefix("0x").unwrap_or(&self.data);
+        hex::decode(data).expect("call data is hex encoded")
+    }
+    pub fn set_data(&mut self, data: String) {
+        self.data = data;
+    }
+    pub fn get_caller(&self) -> Address {
+        self.caller
     }
-    pub fn get_origin(&self) -> String {
-        self.origin.to_string()
+    pub fn get_origin(&self) -> Address {
+        self.origin
     }
-    pub fn get_to(&self) -> String {
-        self.to.to_string()
+    pub fn get_to(&self) -> Address {
+        self.to
     }
     pub fn get_gas_limit(&self) -> &BigUint {
         &self.gas_limit
     }
+    pub fn set_gas_limit(&mut self, gas_limit: BigUint) {
+        self.gas_limit = gas_limit;
+    }
     pub fn get_gas_price(&self) -> &BigUint {
         &self.gas_price
     }
+    pub fn get_blob_hashes(&self) -> &[B256] {
+        &self.blob_hashes
+    }
+    pub fn set_blob_hashes(&mut self, blob_hashes: Vec<B256>) {
+        self.blob_hashes = blob_hashes;
+    }
 }
@@ -0,0 +1,722 @@
+/// Merkle Patricia Trie
+/// 以太坊的状态树和存储树：键按半字节(nibble)组织为叶子、扩展和分支三种节点，
+/// 节点经RLP编码后不足32字节时直接内嵌在父节点中，否则以keccak256哈希引用。
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+
+use crate::impl_rlp;
+use crate::primitives::{Address, B256};
+use crate::rlp::{self, encode_bytes, encode_list_payload, RlpError, RlpItem};
+use crate::utils::keccak256;
+
+/// 空树的根哈希 keccak256(rlp(""))
+pub const EMPTY_ROOT: B256 = B256([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Node {
+    #[default]
+    Empty,
+    Leaf {
+        // 剩余路径（半字节）
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        // 共同前缀（半字节）
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl Node {
+    fn empty_branch() -> Self {
+        Node::Branch {
+            children: Box::default(),
+            value: None,
+        }
+    }
+
+    /// 节点的RLP编码
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Node::Empty => encode_bytes(&[], &mut out),
+            Node::Leaf { path, value } => {
+                let mut payload = Vec::new();
+                encode_bytes(&hex_prefix_encode(path, true), &mut payload);
+                encode_bytes(value, &mut payload);
+                encode_list_payload(&payload, &mut out);
+            }
+            Node::Extension { path, child } => {
+                let mut payload = Vec::new();
+                encode_bytes(&hex_prefix_encode(path, false), &mut payload);
+                child.append_reference(&mut payload);
+                encode_list_payload(&payload, &mut out);
+            }
+            Node::Branch { children, value } => {
+                let mut payload = Vec::new();
+                for child in children.iter() {
+                    child.append_reference(&mut payload);
+                }
+                encode_bytes(value.as_deref().unwrap_or_default(), &mut payload);
+                encode_list_payload(&payload, &mut out);
+            }
+        }
+        out
+    }
+
+    /// 父节点中对子节点的引用，编码不足32字节时内嵌，否则为哈希
+    fn append_reference(&self, out: &mut Vec<u8>) {
+        let encoded = self.encode();
+        if encoded.len() < 32 {
+            out.extend_from_slice(&encoded);
+        } else {
+            encode_bytes(&keccak256(&encoded), out);
+        }
+    }
+}
+
+/// 字节转为半字节路径
+pub fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+/// Hex-Prefix编码，首个半字节标记节点类型（叶子/扩展）和路径长度的奇偶
+pub fn hex_prefix_encode(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        out.push((pair[0] << 4) | pair[1]);
+    }
+    out
+}
+
+/// Hex-Prefix解码，返回(半字节路径, 是否为叶子)
+pub fn hex_prefix_decode(bytes: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let first = *bytes.first()?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return None;
+    }
+    let mut nibbles = Vec::new();
+    if flag % 2 == 1 {
+        nibbles.push(first & 0x0f);
+    } else if first & 0x0f != 0 {
+        return None;
+    }
+    nibbles.extend(to_nibbles(&bytes[1..]));
+    Some((nibbles, flag >= 2))
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn concat(a: &[u8], b: &[u8]) -> Vec<u8> {
+    [a, b].concat()
+}
+
+fn insert_node(node: Node, path: &[u8], value: Vec<u8>) -> Node {
+    match node {
+        Node::Empty => Node::Leaf {
+            path: path.to_vec(),
+            value,
+        },
+        Node::Leaf {
+            path: leaf_path,
+            value: leaf_value,
+        } => {
+            let common = common_prefix(&leaf_path, path);
+            if common == leaf_path.len() && common == path.len() {
+                return Node::Leaf {
+                    path: leaf_path,
+                    value,
+                };
+            }
+            // 在分叉处拆分为分支节点
+            let branch = insert_node(Node::empty_branch(), &leaf_path[common..], leaf_value);
+            let branch = insert_node(branch, &path[common..], value);
+            if common == 0 {
+                branch
+            } else {
+                Node::Extension {
+                    path: path[..common].to_vec(),
+                    child: Box::new(branch),
+                }
+            }
+        }
+        Node::Extension {
+            path: ext_path,
+            child,
+        } => {
+            let common = common_prefix(&ext_path, path);
+            if common == ext_path.len() {
+                return Node::Extension {
+                    child: Box::new(insert_node(*child, &path[common..], value)),
+                    path: ext_path,
+                };
+            }
+            // 扩展节点在分叉处拆分
+            let mut branch = Node::empty_branch();
+            if let Node::Branch { children, .. } = &mut branch {
+                children[ext_path[common] as usize] = if ext_path.len() - common == 1 {
+                    *child
+                } else {
+                    Node::Extension {
+                        path: ext_path[common + 1..].to_vec(),
+                        child,
+                    }
+                };
+            }
+            let branch = insert_node(branch, &path[common..], value);
+            if common == 0 {
+                branch
+            } else {
+                Node::Extension {
+                    path: ext_path[..common].to_vec(),
+                    child: Box::new(branch),
+                }
+            }
+        }
+        Node::Branch {
+            mut children,
+            value: branch_value,
+        } => {
+            if path.is_empty() {
+                return Node::Branch {
+                    children,
+                    value: Some(value),
+                };
+            }
+            let index = path[0] as usize;
+            let child = std::mem::take(&mut children[index]);
+            children[index] = insert_node(child, &path[1..], value);
+            Node::Branch {
+                children,
+                value: branch_value,
+            }
+        }
+    }
+}
+
+fn delete_node(node: Node, path: &[u8]) -> (Node, Option<Vec<u8>>) {
+    match node {
+        Node::Empty => (Node::Empty, None),
+        Node::Leaf {
+            path: leaf_path,
+            value,
+        } => {
+            if leaf_path == path {
+                (Node::Empty, Some(value))
+            } else {
+                (
+                    Node::Leaf {
+                        path: leaf_path,
+                        value,
+                    },
+                    None,
+                )
+            }
+        }
+        Node::Extension {
+            path: ext_path,
+            child,
+        } => {
+            if !path.starts_with(&ext_path) {
+                return (
+                    Node::Extension {
+                        path: ext_path,
+                        child,
+                    },
+                    None,
+                );
+            }
+            let (child, removed) = delete_node(*child, &path[ext_path.len()..]);
+            // 子节点缩减后与扩展节点的路径合并
+            let node = match child {
+                Node::Empty => Node::Empty,
+                Node::Leaf { path, value } => Node::Leaf {
+                    path: concat(&ext_path, &path),
+                    value,
+                },
+                Node::Extension { path, child } => Node::Extension {
+                    path: concat(&ext_path, &path),
+                    child,
+                },
+                branch => Node::Extension {
+                    path: ext_path,
+                    child: Box::new(branch),
+                },
+            };
+            (node, removed)
+        }
+        Node::Branch {
+            mut children,
+            mut value,
+        } => {
+            let removed = if path.is_empty() {
+                value.take()
+            } else {
+                let index = path[0] as usize;
+                let (child, removed) =
+                    delete_node(std::mem::take(&mut children[index]), &path[1..]);
+                children[index] = child;
+                removed
+            };
+            (normalize_branch(children, value), removed)
+        }
+    }
+}
+
+/// 删除后分支节点只剩一个子节点或只剩值时，收缩为叶子或扩展节点
+fn normalize_branch(mut children: Box<[Node; 16]>, value: Option<Vec<u8>>) -> Node {
+    let used: Vec<usize> = (0..16).filter(|i| children[*i] != Node::Empty).collect();
+    match (used.len(), value) {
+        (0, Some(value)) => Node::Leaf {
+            path: Vec::new(),
+            value,
+        },
+        (1, None) => {
+            let index = used[0];
+            match std::mem::take(&mut children[index]) {
+                Node::Leaf { path, value } => Node::Leaf {
+                    path: concat(&[index as u8], &path),
+                    value,
+                },
+                Node::Extension { path, child } => Node::Extension {
+                    path: concat(&[index as u8], &path),
+                    child,
+                },
+                branch => Node::Extension {
+                    path: vec![index as u8],
+                    child: Box::new(branch),
+                },
+            }
+        }
+        (0, None) => Node::Empty,
+        (_, value) => Node::Branch { children, value },
+    }
+}
+
+/// 内存中的Merkle Patricia Trie
+/// ```
+/// use mini_evm::trie::MerklePatriciaTrie;
+/// let mut trie = MerklePatriciaTrie::new();
+/// trie.insert(b"dog", b"puppy".to_vec());
+/// assert_eq!(trie.get(b"dog"), Some(&b"puppy"[..]));
+/// trie.delete(b"dog");
+/// assert!(trie.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MerklePatriciaTrie {
+    root: Node,
+}
+
+impl MerklePatriciaTrie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == Node::Empty
+    }
+
+    /// 插入键值，值为空时等同于删除
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        if value.is_empty() {
+            self.delete(key);
+            return;
+        }
+        let root = std::mem::take(&mut self.root);
+        self.root = insert_node(root, &to_nibbles(key), value);
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let nibbles = to_nibbles(key);
+        let mut path = &nibbles[..];
+        let mut node = &self.root;
+        loop {
+            match node {
+                Node::Empty => return None,
+                Node::Leaf {
+                    path: leaf_path,
+                    value,
+                } => {
+                    return (leaf_path == path).then_some(&value[..]);
+                }
+                Node::Extension {
+                    path: ext_path,
+                    child,
+                } => {
+                    path = path.strip_prefix(&ext_path[..])?;
+                    node = child;
+                }
+                Node::Branch { children, value } => match path.split_first() {
+                    None => return value.as_deref(),
+                    Some((index, rest)) => {
+                        node = &children[*index as usize];
+                        path = rest;
+                    }
+                },
+            }
+        }
+    }
+
+    /// 生成键的Merkle证明：从根节点到键所在位置路径上的节点编码，
+    /// 内嵌在父节点中的节点不单独列出。键不存在时证明其不存在。
+    pub fn get_proof(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let nibbles = to_nibbles(key);
+        let mut path = &nibbles[..];
+        let mut node = &self.root;
+        let mut proof = Vec::new();
+        loop {
+            let encoded = node.encode();
+            if proof.is_empty() || encoded.len() >= 32 {
+                proof.push(encoded);
+            }
+            match node {
+                Node::Empty | Node::Leaf { .. } => return proof,
+                Node::Extension {
+                    path: ext_path,
+                    child,
+                } => match path.strip_prefix(&ext_path[..]) {
+                    Some(rest) => {
+                        path = rest;
+                        node = child;
+                    }
+                    None => return proof,
+                },
+                Node::Branch { children, .. } => match path.split_first() {
+                    None => return proof,
+                    Some((index, rest)) => {
+                        node = &children[*index as usize];
+                        path = rest;
+                    }
+                },
+            }
+        }
+    }
+
+    /// 删除键，返回被删除的值
+    pub fn delete(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let root = std::mem::take(&mut self.root);
+        let (root, removed) = delete_node(root, &to_nibbles(key));
+        self.root = root;
+        removed
+    }
+
+    /// 根哈希，根节点即使不足32字节也取哈希
+    pub fn root_hash(&self) -> B256 {
+        B256::from(keccak256(&self.root.encode()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    // 证明中缺少哈希对应的节点
+    MissingNode(B256),
+    // 节点不是合法的叶子、扩展或分支节点
+    InvalidNode,
+    Rlp(RlpError),
+    // 证明的值与声明的值不一致
+    ValueMismatch,
+}
+
+impl From<RlpError> for ProofError {
+    fn from(err: RlpError) -> Self {
+        ProofError::Rlp(err)
+    }
+}
+
+/// 解析子节点引用：32字节为节点哈希，列表为内嵌节点，空字节串为空节点
+fn resolve_reference(
+    item: &RlpItem,
+    nodes: &HashMap<[u8; 32], &[u8]>,
+) -> Result<RlpItem, ProofError> {
+    match item {
+        RlpItem::Bytes(hash) if hash.len() == 32 => {
+            let hash = B256::from_slice(hash);
+            let encoded = nodes.get(&hash.0).ok_or(ProofError::MissingNode(hash))?;
+            Ok(RlpItem::decode(encoded)?)
+        }
+        RlpItem::Bytes(bytes) if bytes.is_empty() => Ok(item.clone()),
+        RlpItem::List(_) => Ok(item.clone()),
+        RlpItem::Bytes(_) => Err(ProofError::InvalidNode),
+    }
+}
+
+/// 验证Merkle证明，返回键对应的值，键不存在时返回None
+/// ```
+/// use mini_evm::trie::{verify_proof, MerklePatriciaTrie};
+/// let mut trie = MerklePatriciaTrie::new();
+/// trie.insert(b"dog", b"puppy".to_vec());
+/// let proof = trie.get_proof(b"dog");
+/// let value = verify_proof(&trie.root_hash(), b"dog", &proof).unwrap();
+/// assert_eq!(value, Some(b"puppy".to_vec()));
+/// ```
+pub fn verify_proof(
+    root: &B256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, ProofError> {
+    if *root == EMPTY_ROOT {
+        return Ok(None);
+    }
+    let nodes: HashMap<[u8; 32], &[u8]> = proof
+        .iter()
+        .map(|node| (keccak256(node), &node[..]))
+        .collect();
+    let nibbles = to_nibbles(key);
+    let mut path = &nibbles[..];
+    let mut item = resolve_reference(&RlpItem::Bytes(root.0.to_vec()), &nodes)?;
+    loop {
+        match item {
+            RlpItem::Bytes(bytes) if bytes.is_empty() => return Ok(None),
+            RlpItem::List(items) if items.len() == 17 => match path.split_first() {
+                None => {
+                    let value = items[16].as_bytes()?;
+                    return Ok((!value.is_empty()).then(|| value.to_vec()));
+                }
+                Some((index, rest)) => {
+                    item = resolve_reference(&items[*index as usize], &nodes)?;
+                    path = rest;
+                }
+            },
+            RlpItem::List(items) if items.len() == 2 => {
+                let (node_path, is_leaf) =
+                    hex_prefix_decode(items[0].as_bytes()?).ok_or(ProofError::InvalidNode)?;
+                if is_leaf {
+                    if node_path != path {
+                        return Ok(None);
+                    }
+                    return Ok(Some(items[1].as_bytes()?.to_vec()));
+                }
+                match path.strip_prefix(&node_path[..]) {
+                    Some(rest) => {
+                        item = resolve_reference(&items[1], &nodes)?;
+                        path = rest;
+                    }
+                    None => return Ok(None),
+                }
+            }
+            _ => return Err(ProofError::InvalidNode),
+        }
+    }
+}
+
+/// 状态树中的账户，键为keccak256(地址)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrieAccount {
+    pub nonce: BigUint,
+    pub balance: BigUint,
+    pub storage_root: B256,
+    pub code_hash: B256,
+}
+
+impl_rlp!(TrieAccount {
+    nonce,
+    balance,
+    storage_root,
+    code_hash
+});
+
+/// 账户存储树，键为keccak256(32字节槽位)，值为rlp(槽位值)，值为0的槽不写入
+pub fn storage_trie(storage: &HashMap<BigUint, BigUint>) -> MerklePatriciaTrie {
+    let mut trie = MerklePatriciaTrie::new();
+    for (slot, value) in storage {
+        if *value != BigUint::from(0u8) {
+            trie.insert(
+                &keccak256(B256::from_word(slot).as_bytes()),
+                rlp::encode(value),
+            );
+        }
+    }
+    trie
+}
+
+/// 世界状态树
+pub fn state_trie<'a>(
+    accounts: impl IntoIterator<Item = (&'a Address, TrieAccount)>,
+) -> MerklePatriciaTrie {
+    let mut trie = MerklePatriciaTrie::new();
+    for (address, account) in accounts {
+        trie.insert(&keccak256(address.as_bytes()), rlp::encode(&account));
+    }
+    trie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use crate::fake_db::AccountDb;
+    use crate::transaction::Transaction;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_empty_root() {
+        let trie = MerklePatriciaTrie::new();
+        assert_eq!(trie.root_hash(), EMPTY_ROOT);
+        assert_eq!(B256::from(keccak256(&rlp::encode(""))), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_root_hash() {
+        // ethereum/tests TrieTests 中的测试向量
+        let mut trie = MerklePatriciaTrie::new();
+        for (key, value) in [
+            ("do", "verb"),
+            ("dog", "puppy"),
+            ("doge", "coin"),
+            ("horse", "stallion"),
+        ] {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        assert_eq!(
+            trie.root_hash().to_string(),
+            "0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+        assert_eq!(trie.get(b"doge"), Some(&b"coin"[..]));
+        assert_eq!(trie.get(b"dogs"), None);
+        assert_eq!(trie.get(b"d"), None);
+
+        let mut trie = MerklePatriciaTrie::new();
+        for (key, value) in [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ] {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        assert_eq!(
+            trie.root_hash().to_string(),
+            "0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let pairs = [
+            ("do", "verb"),
+            ("dog", "puppy"),
+            ("doge", "coin"),
+            ("horse", "stallion"),
+        ];
+        let mut trie = MerklePatriciaTrie::new();
+        for (key, value) in pairs {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        let full_root = trie.root_hash();
+
+        // 删除后再插入，根哈希与插入顺序无关
+        trie.insert(b"dogs", b"bark".to_vec());
+        assert_eq!(trie.delete(b"dogs"), Some(b"bark".to_vec()));
+        assert_eq!(trie.delete(b"dogs"), None);
+        assert_eq!(trie.root_hash(), full_root);
+
+        let mut expected = MerklePatriciaTrie::new();
+        for (key, value) in [("do", "verb"), ("horse", "stallion")] {
+            expected.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        trie.delete(b"dog");
+        trie.insert(b"doge", Vec::new());
+        assert_eq!(trie.root_hash(), expected.root_hash());
+
+        for (key, _) in pairs {
+            trie.delete(key.as_bytes());
+        }
+        assert!(trie.is_empty());
+        assert_eq!(trie.root_hash(), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_state_root() {
+        Lazy::force(&INIT_LOG);
+        let db = Rc::new(RefCell::new(AccountDb::mock()));
+        let before = AccountDb::mock().state_root();
+        // SSTORE slot1 = 0xf1
+        let bytes = hex::decode("60f1600155").unwrap();
+        let txn = Transaction::mock();
+        let mut evm_test = Evm::init_evm_with_db(bytes, txn.clone(), db.clone());
+        evm_test.run();
+        // 执行后数据库的状态根与直接构造的状态一致
+        let mut expected = AccountDb::mock();
+        let mut account = expected.get_account(txn.get_this_addr()).clone();
+        account
+            .storage
+            .insert(BigUint::from(1u8), BigUint::from(0xf1u8));
+        let storage_root = storage_trie(&account.storage).root_hash();
+        expected.insert(txn.get_this_addr(), account);
+        assert_ne!(expected.state_root(), before);
+        assert_ne!(storage_root, EMPTY_ROOT);
+        assert_eq!(expected.storage_root(&txn.get_this_addr()), storage_root);
+        assert_eq!(db.borrow().state_root(), expected.state_root());
+    }
+
+    #[test]
+    fn test_state_root_vector() {
+        // 多个账户、含存储的固定状态根，由独立实现计算
+        let db = AccountDb::load_json(include_str!("../fixtures/state_root_alloc.json")).unwrap();
+        assert_eq!(
+            db.state_root().to_string(),
+            "0xff3d0f6d2eeb74011aa3dedb53491e5a1b1b3a4e69fd1e7afb0b7693943f8f91"
+        );
+        for (address, root) in [
+            (
+                "0x095e7baea6a6c7c4c2dfeb977efac326af552d87",
+                "0xc1c95f3325c3810f0e6306e60a6117dac307ced9db466dbf984fc6f897ba62e4",
+            ),
+            (
+                "0xc0de000000000000000000000000000000000001",
+                "0x17efaca9d977b441906a6f693fb02bd84c01f3d9c30fbbabb222c1d10799e112",
+            ),
+            (
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b",
+                &EMPTY_ROOT.to_string(),
+            ),
+        ] {
+            assert_eq!(db.storage_root(&address.parse().unwrap()).to_string(), root);
+        }
+    }
+
+    #[test]
+    fn test_hex_prefix() {
+        assert_eq!(
+            hex_prefix_encode(&[1, 2, 3, 4, 5], false),
+            vec![0x11, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix_encode(&[0, 1, 2, 3, 4, 5], false),
+            vec![0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix_encode(&[0x0f, 1, 0x0c, 0x0b, 8], true),
+            vec![0x3f, 0x1c, 0xb8]
+        );
+        assert_eq!(
+            hex_prefix_decode(&[0x20, 0x0f, 0x1c, 0xb8]),
+            Some((vec![0, 0x0f, 1, 0x0c, 0x0b, 8], true))
+        );
+    }
+}
@@ -0,0 +1,792 @@
+/// 已签名交易的EIP-2718类型化封装
+/// 旧交易直接编码为RLP列表，类型化交易编码为 类型字节 || RLP列表。
+/// 支持legacy、EIP-2930、EIP-1559、EIP-4844（不含blob sidecar的规范形式）和EIP-7702交易，
+/// 解码后可以恢复发送者并转换为虚拟机使用的Transaction。
+use std::fmt;
+
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::impl_rlp;
+use crate::ops::contract::create_address;
+use crate::precompile::ecrecover::recover_address;
+use crate::primitives::{Address, B256};
+use crate::rlp::{self, Encodable, RlpError, RlpItem};
+use crate::transaction::Transaction;
+use crate::utils::keccak256;
+
+pub const LEGACY_TX_TYPE: u8 = 0x00;
+pub const EIP2930_TX_TYPE: u8 = 0x01;
+pub const EIP1559_TX_TYPE: u8 = 0x02;
+pub const EIP4844_TX_TYPE: u8 = 0x03;
+pub const EIP7702_TX_TYPE: u8 = 0x04;
+/// EIP-7702授权签名的前缀
+pub const AUTHORIZATION_MAGIC: u8 = 0x05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxError {
+    Rlp(RlpError),
+    // 输入为空
+    EmptyInput,
+    // 未知的交易类型
+    UnsupportedType(u8),
+    // 签名无法恢复出发送者
+    InvalidSignature,
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxError::Rlp(err) => write!(f, "rlp error: {}", err),
+            TxError::EmptyInput => write!(f, "empty transaction bytes"),
+            TxError::UnsupportedType(tx_type) => {
+                write!(f, "unsupported transaction type: 0x{:02x}", tx_type)
+            }
+            TxError::InvalidSignature => write!(f, "invalid transaction signature"),
+        }
+    }
+}
+
+impl std::error::Error for TxError {}
+
+impl From<RlpError> for TxError {
+    fn from(err: RlpError) -> Self {
+        TxError::Rlp(err)
+    }
+}
+
+/// 访问列表中的一项（EIP-2930）
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<B256>,
+}
+impl_rlp!(AccessListItem {
+    address,
+    storage_keys
+});
+
+/// EIP-7702中账户对委托代码地址的签名授权
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authorization {
+    pub chain_id: BigUint,
+    pub address: Address,
+    pub nonce: u64,
+    pub y_parity: u64,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(Authorization {
+    chain_id,
+    address,
+    nonce,
+    y_parity,
+    r,
+    s
+});
+
+impl Authorization {
+    /// 授权签名的哈希 keccak256(0x05 || rlp([chain_id, address, nonce]))
+    pub fn signature_hash(&self) -> B256 {
+        let mut payload = Vec::new();
+        self.chain_id.rlp_append(&mut payload);
+        self.address.rlp_append(&mut payload);
+        self.nonce.rlp_append(&mut payload);
+        let mut preimage = vec![AUTHORIZATION_MAGIC];
+        rlp::encode_list_payload(&payload, &mut preimage);
+        B256::from(keccak256(&preimage))
+    }
+
+    /// 恢复授权账户地址
+    pub fn authority(&self) -> Result<Address, TxError> {
+        let y_parity = u8::try_from(self.y_parity).map_err(|_| TxError::InvalidSignature)?;
+        recover(&self.signature_hash(), y_parity, &self.r, &self.s)
+    }
+}
+
+/// 旧交易，v中包含EIP-155的链id
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyTx {
+    pub nonce: BigUint,
+    pub gas_price: BigUint,
+    pub gas_limit: BigUint,
+    // None表示合约创建
+    pub to: Option<Address>,
+    pub value: BigUint,
+    pub data: Vec<u8>,
+    pub v: BigUint,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(LegacyTx {
+    nonce,
+    gas_price,
+    gas_limit,
+    to,
+    value,
+    data,
+    v,
+    r,
+    s
+});
+
+impl LegacyTx {
+    /// EIP-155交易的链id，v为27或28时为None
+    pub fn chain_id(&self) -> Option<BigUint> {
+        if self.v >= BigUint::from(35u8) {
+            Some((&self.v - 35u8) / 2u8)
+        } else {
+            None
+        }
+    }
+}
+
+/// 带访问列表的交易（EIP-2930）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip2930Tx {
+    pub chain_id: BigUint,
+    pub nonce: BigUint,
+    pub gas_price: BigUint,
+    pub gas_limit: BigUint,
+    pub to: Option<Address>,
+    pub value: BigUint,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    pub y_parity: bool,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(Eip2930Tx {
+    chain_id,
+    nonce,
+    gas_price,
+    gas_limit,
+    to,
+    value,
+    data,
+    access_list,
+    y_parity,
+    r,
+    s
+});
+
+/// 动态手续费交易（EIP-1559）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip1559Tx {
+    pub chain_id: BigUint,
+    pub nonce: BigUint,
+    pub max_priority_fee_per_gas: BigUint,
+    pub max_fee_per_gas: BigUint,
+    pub gas_limit: BigUint,
+    pub to: Option<Address>,
+    pub value: BigUint,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    pub y_parity: bool,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(Eip1559Tx {
+    chain_id,
+    nonce,
+    max_priority_fee_per_gas,
+    max_fee_per_gas,
+    gas_limit,
+    to,
+    value,
+    data,
+    access_list,
+    y_parity,
+    r,
+    s
+});
+
+/// blob交易（EIP-4844），不能用于创建合约
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip4844Tx {
+    pub chain_id: BigUint,
+    pub nonce: BigUint,
+    pub max_priority_fee_per_gas: BigUint,
+    pub max_fee_per_gas: BigUint,
+    pub gas_limit: BigUint,
+    pub to: Address,
+    pub value: BigUint,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    pub max_fee_per_blob_gas: BigUint,
+    pub blob_versioned_hashes: Vec<B256>,
+    pub y_parity: bool,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(Eip4844Tx {
+    chain_id,
+    nonce,
+    max_priority_fee_per_gas,
+    max_fee_per_gas,
+    gas_limit,
+    to,
+    value,
+    data,
+    access_list,
+    max_fee_per_blob_gas,
+    blob_versioned_hashes,
+    y_parity,
+    r,
+    s
+});
+
+/// 设置账户代码委托的交易（EIP-7702），不能用于创建合约
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip7702Tx {
+    pub chain_id: BigUint,
+    pub nonce: BigUint,
+    pub max_priority_fee_per_gas: BigUint,
+    pub max_fee_per_gas: BigUint,
+    pub gas_limit: BigUint,
+    pub to: Address,
+    pub value: BigUint,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    pub authorization_list: Vec<Authorization>,
+    pub y_parity: bool,
+    pub r: BigUint,
+    pub s: BigUint,
+}
+impl_rlp!(Eip7702Tx {
+    chain_id,
+    nonce,
+    max_priority_fee_per_gas,
+    max_fee_per_gas,
+    gas_limit,
+    to,
+    value,
+    data,
+    access_list,
+    authorization_list,
+    y_parity,
+    r,
+    s
+});
+
+/// 已签名交易
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxEnvelope {
+    Legacy(LegacyTx),
+    Eip2930(Eip2930Tx),
+    Eip1559(Eip1559Tx),
+    Eip4844(Eip4844Tx),
+    Eip7702(Eip7702Tx),
+}
+
+/// secp256k1曲线阶的一半，交易签名的s不能超过该值（EIP-2）
+const SECP256K1N_HALF: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// 签名恢复地址，y_parity为0或1
+/// 与ecrecover预编译合约不同，高位s的可延展签名视为无效
+fn recover(hash: &B256, y_parity: u8, r: &BigUint, s: &BigUint) -> Result<Address, TxError> {
+    if s > &BigUint::from_bytes_be(&SECP256K1N_HALF) {
+        return Err(TxError::InvalidSignature);
+    }
+    let word = |value: &BigUint| -> Result<[u8; 32], TxError> {
+        let bytes = value.to_bytes_be();
+        if bytes.len() > 32 {
+            return Err(TxError::InvalidSignature);
+        }
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+        Ok(word)
+    };
+    if y_parity > 1 {
+        return Err(TxError::InvalidSignature);
+    }
+    let hash: [u8; 32] = hash.as_bytes().try_into().unwrap();
+    recover_address(&hash, 27 + y_parity, &word(r)?, &word(s)?)
+        .map(Address::new)
+        .ok_or(TxError::InvalidSignature)
+}
+
+/// 去掉编码结果中最后三个签名字段，返回剩余字段组成的列表
+fn unsigned_fields<T: Encodable>(tx: &T) -> Vec<RlpItem> {
+    let item = RlpItem::decode(&rlp::encode(tx)).expect("encoded transaction is valid rlp");
+    let mut fields = item.as_list().unwrap().to_vec();
+    fields.truncate(fields.len() - 3);
+    fields
+}
+
+impl TxEnvelope {
+    /// 解码EIP-2718格式的已签名交易
+    /// 首字节不小于0xc0时为旧交易的RLP列表，否则为交易类型
+    /// ```
+    /// use mini_evm::tx_envelope::TxEnvelope;
+    /// let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+    /// let tx = TxEnvelope::decode(&raw).unwrap();
+    /// assert_eq!(tx.tx_type(), 0);
+    /// assert_eq!(tx.encode(), raw);
+    /// assert_eq!(
+    ///     tx.recover_sender().unwrap(),
+    ///     "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".parse().unwrap()
+    /// );
+    /// ```
+    pub fn decode(raw: &[u8]) -> Result<Self, TxError> {
+        let tx_type = *raw.first().ok_or(TxError::EmptyInput)?;
+        if tx_type >= 0xc0 {
+            return Ok(TxEnvelope::Legacy(rlp::decode(raw)?));
+        }
+        let payload = &raw[1..];
+        match tx_type {
+            EIP2930_TX_TYPE => Ok(TxEnvelope::Eip2930(rlp::decode(payload)?)),
+            EIP1559_TX_TYPE => Ok(TxEnvelope::Eip1559(rlp::decode(payload)?)),
+            EIP4844_TX_TYPE => Ok(TxEnvelope::Eip4844(rlp::decode(payload)?)),
+            EIP7702_TX_TYPE => Ok(TxEnvelope::Eip7702(rlp::decode(payload)?)),
+            _ => Err(TxError::UnsupportedType(tx_type)),
+        }
+    }
+
+    /// 编码为EIP-2718格式
+    pub fn encode(&self) -> Vec<u8> {
+        let payload = match self {
+            TxEnvelope::Legacy(tx) => return rlp::encode(tx),
+            TxEnvelope::Eip2930(tx) => rlp::encode(tx),
+            TxEnvelope::Eip1559(tx) => rlp::encode(tx),
+            TxEnvelope::Eip4844(tx) => rlp::encode(tx),
+            TxEnvelope::Eip7702(tx) => rlp::encode(tx),
+        };
+        let mut out = vec![self.tx_type()];
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    pub fn tx_type(&self) -> u8 {
+        match self {
+            TxEnvelope::Legacy(_) => LEGACY_TX_TYPE,
+            TxEnvelope::Eip2930(_) => EIP2930_TX_TYPE,
+            TxEnvelope::Eip1559(_) => EIP1559_TX_TYPE,
+            TxEnvelope::Eip4844(_) => EIP4844_TX_TYPE,
+            TxEnvelope::Eip7702(_) => EIP7702_TX_TYPE,
+        }
+    }
+
+    /// 交易哈希，即编码结果的keccak256
+    pub fn hash(&self) -> B256 {
+        B256::from(keccak256(&self.encode()))
+    }
+
+    /// 发送者签名的哈希
+    /// 旧交易对前六个字段签名，EIP-155交易额外附加[chain_id, 0, 0]，
+    /// 类型化交易对 类型字节 || rlp(除签名外的字段) 签名
+    pub fn signature_hash(&self) -> B256 {
+        let fields = match self {
+            TxEnvelope::Legacy(tx) => {
+                let mut fields = unsigned_fields(tx);
+                if let Some(chain_id) = tx.chain_id() {
+                    fields.push(RlpItem::decode(&rlp::encode(&chain_id)).unwrap());
+                    fields.push(RlpItem::Bytes(Vec::new()));
+                    fields.push(RlpItem::Bytes(Vec::new()));
+                }
+                return B256::from(keccak256(&rlp::encode(&RlpItem::List(fields))));
+            }
+            TxEnvelope::Eip2930(tx) => unsigned_fields(tx),
+            TxEnvelope::Eip1559(tx) => unsigned_fields(tx),
+            TxEnvelope::Eip4844(tx) => unsigned_fields(tx),
+            TxEnvelope::Eip7702(tx) => unsigned_fields(tx),
+        };
+        let mut preimage = vec![self.tx_type()];
+        preimage.extend_from_slice(&rlp::encode(&RlpItem::List(fields)));
+        B256::from(keccak256(&preimage))
+    }
+
+    /// 从签名恢复发送者地址
+    pub fn recover_sender(&self) -> Result<Address, TxError> {
+        let (y_parity, r, s) = match self {
+            TxEnvelope::Legacy(tx) => {
+                // v为27/28，或EIP-155的 chain_id * 2 + 35/36
+                let base = match tx.chain_id() {
+                    Some(chain_id) => chain_id * 2u8 + 35u8,
+                    None => BigUint::from(27u8),
+                };
+                let y_parity = if tx.v >= base {
+                    (&tx.v - base).to_u8()
+                } else {
+                    None
+                };
+                let y_parity = y_parity.ok_or(TxError::InvalidSignature)?;
+                (y_parity, &tx.r, &tx.s)
+            }
+            TxEnvelope::Eip2930(tx) => (tx.y_parity as u8, &tx.r, &tx.s),
+            TxEnvelope::Eip1559(tx) => (tx.y_parity as u8, &tx.r, &tx.s),
+            TxEnvelope::Eip4844(tx) => (tx.y_parity as u8, &tx.r, &tx.s),
+            TxEnvelope::Eip7702(tx) => (tx.y_parity as u8, &tx.r, &tx.s),
+        };
+        recover(&self.signature_hash(), y_parity, r, s)
+    }
+
+    /// 链id，未使用EIP-155的旧交易为None
+    pub fn chain_id(&self) -> Option<BigUint> {
+        match self {
+            TxEnvelope::Legacy(tx) => tx.chain_id(),
+            TxEnvelope::Eip2930(tx) => Some(tx.chain_id.clone()),
+            TxEnvelope::Eip1559(tx) => Some(tx.chain_id.clone()),
+            TxEnvelope::Eip4844(tx) => Some(tx.chain_id.clone()),
+            TxEnvelope::Eip7702(tx) => Some(tx.chain_id.clone()),
+        }
+    }
+
+    pub fn nonce(&self) -> &BigUint {
+        match self {
+            TxEnvelope::Legacy(tx) => &tx.nonce,
+            TxEnvelope::Eip2930(tx) => &tx.nonce,
+            TxEnvelope::Eip1559(tx) => &tx.nonce,
+            TxEnvelope::Eip4844(tx) => &tx.nonce,
+            TxEnvelope::Eip7702(tx) => &tx.nonce,
+        }
+    }
+
+    pub fn gas_limit(&self) -> &BigUint {
+        match self {
+            TxEnvelope::Legacy(tx) => &tx.gas_limit,
+            TxEnvelope::Eip2930(tx) => &tx.gas_limit,
+            TxEnvelope::Eip1559(tx) => &tx.gas_limit,
+            TxEnvelope::Eip4844(tx) => &tx.gas_limit,
+            TxEnvelope::Eip7702(tx) => &tx.gas_limit,
+        }
+    }
+
+    /// 接收地址，合约创建交易为None
+    pub fn to(&self) -> Option<Address> {
+        match self {
+            TxEnvelope::Legacy(tx) => tx.to,
+            TxEnvelope::Eip2930(tx) => tx.to,
+            TxEnvelope::Eip1559(tx) => tx.to,
+            TxEnvelope::Eip4844(tx) => Some(tx.to),
+            TxEnvelope::Eip7702(tx) => Some(tx.to),
+        }
+    }
+
+    pub fn value(&self) -> &BigUint {
+        match self {
+            TxEnvelope::Legacy(tx) => &tx.value,
+            TxEnvelope::Eip2930(tx) => &tx.value,
+            TxEnvelope::Eip1559(tx) => &tx.value,
+            TxEnvelope::Eip4844(tx) => &tx.value,
+            TxEnvelope::Eip7702(tx) => &tx.value,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            TxEnvelope::Legacy(tx) => &tx.data,
+            TxEnvelope::Eip2930(tx) => &tx.data,
+            TxEnvelope::Eip1559(tx) => &tx.data,
+            TxEnvelope::Eip4844(tx) => &tx.data,
+            TxEnvelope::Eip7702(tx) => &tx.data,
+        }
+    }
+
+    /// 访问列表，旧交易为空
+    pub fn access_list(&self) -> &[AccessListItem] {
+        match self {
+            TxEnvelope::Legacy(_) => &[],
+            TxEnvelope::Eip2930(tx) => &tx.access_list,
+            TxEnvelope::Eip1559(tx) => &tx.access_list,
+            TxEnvelope::Eip4844(tx) => &tx.access_list,
+            TxEnvelope::Eip7702(tx) => &tx.access_list,
+        }
+    }
+
+    /// 实际的gas价格，动态手续费交易为 min(max_fee, base_fee + max_priority_fee)
+    pub fn effective_gas_price(&self, base_fee: &BigUint) -> BigUint {
+        let dynamic = |max_fee: &BigUint, max_priority_fee: &BigUint| {
+            (base_fee + max_priority_fee).min(max_fee.clone())
+        };
+        match self {
+            TxEnvelope::Legacy(tx) => tx.gas_price.clone(),
+            TxEnvelope::Eip2930(tx) => tx.gas_price.clone(),
+            TxEnvelope::Eip1559(tx) => dynamic(&tx.max_fee_per_gas, &tx.max_priority_fee_per_gas),
+            TxEnvelope::Eip4844(tx) => dynamic(&tx.max_fee_per_gas, &tx.max_priority_fee_per_gas),
+            TxEnvelope::Eip7702(tx) => dynamic(&tx.max_fee_per_gas, &tx.max_priority_fee_per_gas),
+        }
+    }
+
+    /// 转换为虚拟机执行的交易，合约创建交易的执行地址为新合约地址
+    /// ```
+    /// use mini_evm::tx_envelope::TxEnvelope;
+    /// use num_bigint::BigUint;
+    /// let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+    /// let txn = TxEnvelope::decode(&raw).unwrap().to_transaction(&BigUint::from(0u8)).unwrap();
+    /// assert_eq!(txn.get_gas_limit(), &BigUint::from(21000u32));
+    /// assert_eq!(txn.get_this_addr(), txn.get_to());
+    /// ```
+    pub fn to_transaction(&self, base_fee: &BigUint) -> Result<Transaction, TxError> {
+        let sender = self.recover_sender()?;
+        let to = self
+            .to()
+            .unwrap_or_else(|| create_address(&sender, self.nonce()));
+        let mut txn = Transaction::init(
+            self.nonce().clone(),
+            self.effective_gas_price(base_fee),
+            self.gas_limit().clone(),
+            to,
+            self.value().clone(),
+            hex::encode(self.data()),
+            sender,
+            sender,
+            to,
+            BigUint::zero(),
+            BigUint::zero(),
+            BigUint::zero(),
+        );
+        if let TxEnvelope::Eip4844(tx) = self {
+            txn.set_blob_hashes(tx.blob_versioned_hashes.clone());
+        }
+        Ok(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::*;
+    use k256::ecdsa::SigningKey;
+    use once_cell::sync::Lazy;
+
+    // 私钥1对应的地址
+    const SENDER: &str = "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf";
+
+    /// 用私钥1签名，返回(y_parity, r, s)
+    fn sign(hash: &B256) -> (bool, BigUint, BigUint) {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = SigningKey::from_bytes(&secret.into()).unwrap();
+        let (signature, recovery_id) = key.sign_prehash_recoverable(hash.as_bytes()).unwrap();
+        (
+            recovery_id.is_y_odd(),
+            BigUint::from_bytes_be(&signature.r().to_bytes()),
+            BigUint::from_bytes_be(&signature.s().to_bytes()),
+        )
+    }
+
+    fn access_list() -> Vec<AccessListItem> {
+        vec![AccessListItem {
+            address: "0x100000000000000000000000000000000000c0de"
+                .parse()
+                .unwrap(),
+            storage_keys: vec![B256::from([1u8; 32])],
+        }]
+    }
+
+    fn eip1559(to: Option<Address>) -> Eip1559Tx {
+        Eip1559Tx {
+            chain_id: BigUint::from(1u8),
+            nonce: BigUint::from(3u8),
+            max_priority_fee_per_gas: BigUint::from(2u8),
+            max_fee_per_gas: BigUint::from(100u8),
+            gas_limit: BigUint::from(50000u32),
+            to,
+            value: BigUint::from(10u8),
+            data: vec![0xaa, 0xbb],
+            access_list: access_list(),
+            y_parity: false,
+            r: BigUint::zero(),
+            s: BigUint::zero(),
+        }
+    }
+
+    /// 签名并检查编码往返和发送者恢复
+    fn signed(mut envelope: TxEnvelope) -> TxEnvelope {
+        let (y_parity, r, s) = sign(&envelope.signature_hash());
+        match &mut envelope {
+            TxEnvelope::Legacy(_) => unreachable!(),
+            TxEnvelope::Eip2930(tx) => (tx.y_parity, tx.r, tx.s) = (y_parity, r, s),
+            TxEnvelope::Eip1559(tx) => (tx.y_parity, tx.r, tx.s) = (y_parity, r, s),
+            TxEnvelope::Eip4844(tx) => (tx.y_parity, tx.r, tx.s) = (y_parity, r, s),
+            TxEnvelope::Eip7702(tx) => (tx.y_parity, tx.r, tx.s) = (y_parity, r, s),
+        }
+        let raw = envelope.encode();
+        assert_eq!(raw[0], envelope.tx_type());
+        let decoded = TxEnvelope::decode(&raw).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.hash(), B256::from(keccak256(&raw)));
+        assert_eq!(decoded.recover_sender().unwrap(), SENDER.parse().unwrap());
+        decoded
+    }
+
+    #[test]
+    fn test_legacy_eip155() {
+        // EIP-155中的示例交易
+        let raw = hex::decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d899\
+             7f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )
+        .unwrap();
+        let tx = TxEnvelope::decode(&raw).unwrap();
+        assert_eq!(tx.chain_id(), Some(BigUint::from(1u8)));
+        assert_eq!(tx.nonce(), &BigUint::from(9u8));
+        assert_eq!(
+            hex::encode(tx.signature_hash().as_bytes()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        assert_eq!(tx.encode(), raw);
+
+        // v错误时无法恢复发送者
+        let mut invalid = tx.clone();
+        if let TxEnvelope::Legacy(legacy) = &mut invalid {
+            legacy.v = BigUint::from(26u8);
+        }
+        assert_eq!(invalid.recover_sender(), Err(TxError::InvalidSignature));
+
+        // 将s替换为n - s并翻转v得到的可延展签名不被接受
+        let n = BigUint::from_bytes_be(&SECP256K1N_HALF) * 2u8 + 1u8;
+        let mut malleable = tx.clone();
+        if let TxEnvelope::Legacy(legacy) = &mut malleable {
+            legacy.s = &n - &legacy.s;
+            legacy.v = BigUint::from(37u8) + 38u8 - &legacy.v;
+        }
+        assert_eq!(malleable.recover_sender(), Err(TxError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        signed(TxEnvelope::Eip2930(Eip2930Tx {
+            chain_id: BigUint::from(1u8),
+            nonce: BigUint::zero(),
+            gas_price: BigUint::from(7u8),
+            gas_limit: BigUint::from(21000u32),
+            to: None,
+            value: BigUint::zero(),
+            data: vec![0x60, 0x00],
+            access_list: access_list(),
+            y_parity: false,
+            r: BigUint::zero(),
+            s: BigUint::zero(),
+        }));
+        signed(TxEnvelope::Eip1559(eip1559(Some(
+            "0x100000000000000000000000000000000000c0de"
+                .parse()
+                .unwrap(),
+        ))));
+
+        let tx = eip1559(None);
+        let authorization = {
+            let mut authorization = Authorization {
+                chain_id: BigUint::from(1u8),
+                address: "0x100000000000000000000000000000000000c0de"
+                    .parse()
+                    .unwrap(),
+                nonce: 4,
+                y_parity: 0,
+                r: BigUint::zero(),
+                s: BigUint::zero(),
+            };
+            let (y_parity, r, s) = sign(&authorization.signature_hash());
+            authorization.y_parity = y_parity as u64;
+            authorization.r = r;
+            authorization.s = s;
+            authorization
+        };
+        assert_eq!(authorization.authority().unwrap(), SENDER.parse().unwrap());
+        let envelope = signed(TxEnvelope::Eip7702(Eip7702Tx {
+            chain_id: tx.chain_id,
+            nonce: tx.nonce,
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+            max_fee_per_gas: tx.max_fee_per_gas,
+            gas_limit: tx.gas_limit,
+            to: SENDER.parse().unwrap(),
+            value: tx.value,
+            data: tx.data,
+            access_list: tx.access_list,
+            authorization_list: vec![authorization],
+            y_parity: false,
+            r: BigUint::zero(),
+            s: BigUint::zero(),
+        }));
+        assert_eq!(envelope.tx_type(), EIP7702_TX_TYPE);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(TxEnvelope::decode(&[]), Err(TxError::EmptyInput));
+        assert_eq!(
+            TxEnvelope::decode(&[0x05, 0xc0]),
+            Err(TxError::UnsupportedType(0x05))
+        );
+        // 字段个数不符
+        assert_eq!(
+            TxEnvelope::decode(&[0x02, 0xc1, 0x01]),
+            Err(TxError::Rlp(RlpError::ListLength {
+                expected: 12,
+                actual: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn test_to_transaction() {
+        Lazy::force(&INIT_LOG);
+        let tx = eip1559(Some(
+            "0x100000000000000000000000000000000000c0de"
+                .parse()
+                .unwrap(),
+        ));
+        let blob = signed(TxEnvelope::Eip4844(Eip4844Tx {
+            chain_id: tx.chain_id,
+            nonce: tx.nonce,
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+            max_fee_per_gas: tx.max_fee_per_gas,
+            gas_limit: tx.gas_limit,
+            to: tx.to.unwrap(),
+            value: tx.value,
+            data: tx.data,
+            access_list: tx.access_list,
+            max_fee_per_blob_gas: BigUint::from(1u8),
+            blob_versioned_hashes: vec![B256::from([1u8; 32])],
+            y_parity: false,
+            r: BigUint::zero(),
+            s: BigUint::zero(),
+        }));
+        let txn = blob.to_transaction(&BigUint::from(30u8)).unwrap();
+        assert_eq!(txn.get_caller(), SENDER.parse().unwrap());
+        assert_eq!(txn.get_origin(), SENDER.parse().unwrap());
+        assert_eq!(txn.get_this_addr(), tx.to.unwrap());
+        // min(100, 30 + 2)
+        assert_eq!(txn.get_gas_price(), &BigUint::from(32u8));
+        assert_eq!(txn.get_blob_hashes(), &[B256::from([1u8; 32])]);
+        assert_eq!(
+            blob.effective_gas_price(&BigUint::from(99u8)),
+            BigUint::from(100u8)
+        );
+
+        // CALLDATALOAD(0) 读取交易数据
+        let mut evm_test = Evm::init_evm(hex::decode("600035").unwrap(), txn);
+        evm_test.run();
+        assert_eq!(evm_test.stack.get(1).data[..2], [0xaa, 0xbb]);
+
+        // 解码后的交易直接调用SHA-256预编译合约
+        let mut call = eip1559(Some(crate::precompile::precompile_address(2)));
+        call.data = b"abc".to_vec();
+        let raw = signed(TxEnvelope::Eip1559(call)).encode();
+        let txn = TxEnvelope::decode(&raw)
+            .unwrap()
+            .to_transaction(&BigUint::zero())
+            .unwrap();
+        let evm_sub =
+            Evm::init_evm(Vec::new(), txn.clone()).run_sub_call(&txn.get_to(), txn, false);
+        assert!(evm_sub.success);
+        assert_eq!(
+            hex::encode(evm_sub.return_data),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        // 合约创建交易在新合约地址执行
+        let create = signed(TxEnvelope::Eip1559(eip1559(None)));
+        let txn = create.to_transaction(&BigUint::zero()).unwrap();
+        assert_eq!(
+            txn.get_this_addr(),
+            create_address(&SENDER.parse().unwrap(), &BigUint::from(3u8))
+        );
+    }
+}
@@ -1,11 +1,7 @@
-use crate::{
-    const_var::*,
-    fake_db::{self, AccountDb},
-};
+use crate::const_var::*;
 use num_bigint::BigUint;
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, sync::Mutex};
-use std::{fmt::format, sync::MutexGuard};
+use std::collections::HashMap;
 use tiny_keccak::{Hasher, Keccak};
 
 /// 判断是否是有符号的数据
@@ -88,9 +84,12 @@ pub fn get_instruction_name(op: u8) -> String {
         MSTORE => "MSTORE".to_string(),
         MSTORE8 => "MSTORE8".to_string(),
         MSIZE => "MSIZE".to_string(),
+        MCOPY => "MCOPY".to_string(),
         MLOAD => "MLOAD".to_string(),
         SLOAD => "SLOAD".to_string(),
         SSTORE => "SSTORE".to_string(),
+        TLOAD => "TLOAD".to_string(),
+        TSTORE => "TSTORE".to_string(),
         JUMP => "JUMP".to_string(),
         JUMPI => "JUMPI".to_string(),
         PC => "PC".to_string(),
@@ -105,6 +104,8 @@ pub fn get_instruction_name(op: u8) -> String {
         CHAINID => "CHAINID".to_string(),
         SELFBALANCE => "SELFBALANCE".to_string(),
         BASEFEE => "BASEFEE".to_string(),
+        BLOBHASH => "BLOBHASH".to_string(),
+        BLOBBASEFEE => "BLOBBASEFEE".to_string(),
         operation if operation >= DUP1 && operation <= DUP16 => {
             let num = (operation - DUP1 + 1) as usize;
             format!("DUP{}", num)
@@ -128,6 +129,7 @@ pub fn get_instruction_name(op: u8) -> String {
         REVERT => "REVERT".to_string(),
         INVALID => "INVALID".to_string(),
         CALL => "CALL".to_string(),
+        CALLCODE => "CALLCODE".to_string(),
         DELEGATECALL => "DELEGATECALL".to_string(),
         STATICCALL => "STATICCALL".to_string(),
         CREATE => "CREATE".to_string(),
@@ -137,9 +139,6 @@ pub fn get_instruction_name(op: u8) -> String {
         _ => "UNKNOWN".to_string(),
     }
 }
-static FAKE_DB_1: Lazy<Mutex<AccountDb>> = Lazy::new(|| Mutex::new(AccountDb::mock()));
-static FAKE_DB_2: Lazy<Mutex<AccountDb>> = Lazy::new(|| Mutex::new(AccountDb::mock_2()));
-
 pub static GAS_COSTS: Lazy<HashMap<u8, u32>> = Lazy::new(|| {
     let mut m = HashMap::new();
 
@@ -204,10 +203,14 @@ pub static GAS_COSTS: Lazy<HashMap<u8, u32>> = Lazy::new(|| {
     m.insert(CHAINID, 2);
     m.insert(SELFBALANCE, 2);
     m.insert(BASEFEE, 2);
+    m.insert(BLOBHASH, 3);
+    m.insert(BLOBBASEFEE, 2);
 
     // 存储操作
     m.insert(SLOAD, 100);
     m.insert(SSTORE, 100); // 动态部分成本
+    m.insert(TLOAD, 100);
+    m.insert(TSTORE, 100);
 
     // 流程控制
     m.insert(JUMP, 8);
@@ -220,6 +223,7 @@ pub static GAS_COSTS: Lazy<HashMap<u8, u32>> = Lazy::new(|| {
     // 内存操作
     m.insert(MLOAD, 3);
     m.insert(MSTORE, 3);
+    m.insert(MCOPY, 3); // 动态部分成本
     m.insert(MSTORE8, 3);
 
     // 日志操作
@@ -232,6 +236,7 @@ pub static GAS_COSTS: Lazy<HashMap<u8, u32>> = Lazy::new(|| {
     // 系统操作
     m.insert(CREATE, 32000);
     m.insert(CALL, 700); // 动态部分成本
+    m.insert(CALLCODE, 700); // 动态部分成本
     m.insert(RETURN, 0); // 动态部分成本
     m.insert(DELEGATECALL, 700); // 动态部分成本
     m.insert(CREATE2, 32000); // 动态部分成本
@@ -249,10 +254,3 @@ pub static GAS_COSTS: Lazy<HashMap<u8, u32>> = Lazy::new(|| {
     }
     m
 });
-pub fn get_account_db() -> MutexGuard<'static, AccountDb> {
-    FAKE_DB_1.lock().unwrap()
-}
-
-pub fn get_account_db_2() -> MutexGuard<'static, AccountDb> {
-    FAKE_DB_2.lock().unwrap()
-}